@@ -6,7 +6,8 @@ impl Widget for Greeter {
     fn update(&mut self) -> Option<WidgetUpdate> {
         Some(WidgetUpdate {
             refresh_interval: std::time::Duration::new(1, 0),
-            data: Some(Block::new().append_full_text(self.0).clone()),
+            aligned: false,
+            data: Some(Block::new().append_full_text(self.0).clone().into()),
         })
     }
 }
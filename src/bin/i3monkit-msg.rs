@@ -0,0 +1,54 @@
+//! Send a command to a running i3monkit bar
+//!
+//! ```text
+//! i3monkit-msg [-s SOCKET] COMMAND [ARGS...]
+//! ```
+//!
+//! See the `ipc` module for the commands.
+
+use i3monkit::ipc;
+
+use std::path::PathBuf;
+use std::process::exit;
+
+const USAGE: &str = "Usage: i3monkit-msg [-s SOCKET] COMMAND [ARGS...]
+
+Commands:
+    list                            Show the widgets and their blocks
+    refresh <widget>                Update the widget right away
+    click <widget> [button]         Click the widget
+    click-block <button> <name> [instance]
+                                    Click the block, with the name and instance percent-encoded
+    override <widget> <secs> <text> Show the text in place of the widget, 0 secs for ever
+    clear <widget>                  Clear the override text
+    pause                           Stop updating the bar
+    resume                          Resume updating the bar
+    reload                          Reload the configuration file";
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let mut path = ipc::default_socket_path();
+
+    if args.len() >= 2 && (args[0] == "-s" || args[0] == "--socket") {
+        path = PathBuf::from(&args[1]);
+        args.drain(..2);
+    }
+
+    if args.is_empty() || args[0] == "-h" || args[0] == "--help" {
+        eprintln!("{}", USAGE);
+        exit(2);
+    }
+
+    match ipc::request(&path, &args.join(" ")) {
+        Ok(response) => {
+            println!("{}", response);
+            if response.starts_with("error") {
+                exit(1);
+            }
+        }
+        Err(e) => {
+            eprintln!("i3monkit-msg: {}: {}", path.display(), e);
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,70 @@
+//! The status bar program built from a configuration file
+//!
+//! ```text
+//! i3monkit [-c CONFIG] [--once]
+//! ```
+//!
+//! The configuration is read from `$XDG_CONFIG_HOME/i3monkit/config.toml` by default, see the
+//! `config` module for the format. The widgets are reloaded whenever the configuration file changes,
+//! or the program receives SIGHUP.
+//!
+//! With `--once`, the widgets are updated once and the bar is printed before the program exits,
+//! which is how the tmux status line runs the program with `#(i3monkit --once)`.
+
+use i3monkit::config::{default_config_path, Config};
+use i3monkit::WidgetRegistry;
+
+use std::path::PathBuf;
+use std::process::exit;
+
+const USAGE: &str = "Usage: i3monkit [-c CONFIG] [--once]";
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut path = None;
+    let mut once = false;
+
+    while let Some(arg) = args.next() {
+        let config = match arg.as_str() {
+            "-c" | "--config" if path.is_none() => args.next(),
+            "--once" => {
+                once = true;
+                continue;
+            }
+            _ => None,
+        };
+
+        match config {
+            Some(config) => path = Some(PathBuf::from(config)),
+            None => {
+                eprintln!("{}", USAGE);
+                exit(2);
+            }
+        }
+    }
+
+    let path = path.unwrap_or_else(default_config_path);
+
+    let result = Config::load(&path).and_then(|config| {
+        let bar = if once {
+            config.build_once()?
+        } else {
+            config.build_watched(&path, WidgetRegistry::new())?
+        };
+        Ok((bar, config))
+    });
+
+    let (mut bar, config) = match result {
+        Ok(bar) => bar,
+        Err(e) => {
+            eprintln!("i3monkit: {}: {}", path.display(), e);
+            exit(1);
+        }
+    };
+
+    if once {
+        config.run_once(&mut bar);
+    } else {
+        config.run(&mut bar);
+    }
+}
@@ -0,0 +1,24 @@
+//! The clocks used by the scheduler
+
+use std::time::Duration;
+
+fn read_clock(clock: libc::clockid_t) -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe {
+        libc::clock_gettime(clock, &mut ts);
+    }
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+/// The total time the system has been suspended since boot
+///
+/// `CLOCK_MONOTONIC`, which `Instant` is based on, stops while the system is suspended, but
+/// `CLOCK_BOOTTIME` doesn't. So the difference between them grows with each suspension.
+pub(crate) fn suspended_time() -> Duration {
+    read_clock(libc::CLOCK_BOOTTIME)
+        .checked_sub(read_clock(libc::CLOCK_MONOTONIC))
+        .unwrap_or_default()
+}
@@ -0,0 +1,433 @@
+//! Build a bar from a TOML configuration file
+//!
+//! This is what the `i3monkit` program runs, so a bar made of the builtin widgets doesn't need any
+//! Rust code. The configuration looks like:
+//!
+//! ```toml
+//! [bar]
+//! click_events = true
+//! ipc = true
+//!
+//! [[widget]]
+//! type = "network"
+//! interface = "wlp58s0"
+//!
+//! [[widget]]
+//! type = "cpu"
+//! core = 0
+//!
+//! [[widget]]
+//! type = "volume"
+//! signal = 3
+//!
+//! [[widget]]
+//! type = "datetime"
+//! name = "clock"
+//! color = "#00ee22"
+//! ```
+//!
+//! The `bar` section accepts:
+//!
+//! - `click_events` If the clicks on the blocks are handled, true by default
+//! - `ipc` If the control server is started, false by default
+//! - `socket` The path of the control server socket, `$XDG_RUNTIME_DIR/i3monkit.sock` by default
+//! - `output` The status bar the program writes to, `i3bar` by default, see `Output`
+//! - `file` The file the tmux status line is written to instead of the stdout
+//!
+//! The `i3monkit` program reloads the widgets whenever the file changes or it receives SIGHUP,
+//! see `Config::build_watched`.
+//!
+//! The widgets are shown from the left to the right in the order of the `widget` sections. Every
+//! widget accepts the following options:
+//!
+//! - `type` The type of the widget, which is required
+//! - `name` The name of the widget, see `WidgetCollection::push_named`
+//! - `color` The color of the text, in `#rrggbb` format
+//! - `background` The color of the background, in `#rrggbb` format
+//! - `interval` The refresh interval in seconds, which overrides the default of the widget
+//! - `signal` Update the widget when the program receives `SIGRTMIN+signal`
+//! - `timeout` How long in seconds an update may take before the block is marked stale, for the
+//!   widget types updated on their own thread, see `WidgetRegistry::register_threaded`
+//!
+//! The builtin widget types and their own options are listed below, more types can be added with a
+//! `WidgetRegistry`.
+//!
+//! - `cpu` The usage of a CPU core, with `core` being the index of the core
+//! - `network` The speed of a network `interface`, with `hide_when_down` to hide the widget while
+//!   the interface is down
+//! - `battery` The status of the battery, with `index` being the index of the battery, 0 by
+//!   default, and `hide_when_full` to hide the widget while the battery is full
+//! - `volume` The volume of the ALSA `device` and `mixer`, "default" and "Master" by default, with
+//!   `index` being the index of the mixer, 0 by default
+//! - `datetime` The current date and time
+//! - `command` The output of a shell `command`, following the conventions of i3blocks, with
+//!   `instance` being the instance of the block, and `pango` to render the output as Pango markup.
+//!   The command is run every 5 seconds by default, on its own thread so that a slow command
+//!   doesn't hold up the bar. Its block is marked stale after a `timeout` of 5 seconds by default
+//! - `stream` The lines printed by a shell `command` that keeps running, with `pango` to render the
+//!   lines as Pango markup. The command is restarted when it exits
+//! - `i3status` The blocks of i3status, or of any other status program given as `command`, shown in
+//!   place of the widget
+//! - `stock` The stock prices of the `symbols` queried with the Alpha Vantage `api_key`, one block
+//!   per symbol
+
+use crate::error::{Error, Result};
+use crate::ipc::default_socket_path;
+use crate::output::BarOutput;
+use crate::outputs::{LemonbarOutput, TmuxOutput};
+use crate::protocol::{Block, ClickEvent, ColorRGB, Header, I3Protocol};
+use crate::registry::{Created, WidgetRegistry};
+use crate::watch::watch_file;
+use crate::widget::{
+    realtime_signal, LayoutEntry, LayoutWidget, Notifier, Widget, WidgetCollection, WidgetUpdate,
+};
+
+use serde::{Deserialize, Serialize};
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// The configuration of a bar
+#[derive(Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    bar: BarConfig,
+    #[serde(default, rename = "widget")]
+    widgets: Vec<WidgetConfig>,
+}
+
+/// The status bar the program writes to
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Output {
+    /// i3bar or swaybar, which read the JSON protocol from the stdout
+    I3bar,
+    /// lemonbar, which reads the stdout, and whose output is read from the stdin for the clicks
+    Lemonbar,
+    /// A polybar `custom/script` module with `tail = true`, which reads the stdout. The clicks
+    /// are delivered with `i3monkit-msg` when the control server is enabled
+    Polybar,
+    /// The tmux status line, which is written to the stdout, or to the `file` of the `bar` section
+    Tmux,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct BarConfig {
+    click_events: bool,
+    ipc: bool,
+    socket: Option<PathBuf>,
+    output: Output,
+    file: Option<PathBuf>,
+}
+
+impl Default for BarConfig {
+    fn default() -> Self {
+        BarConfig {
+            click_events: true,
+            ipc: false,
+            socket: None,
+            output: Output::I3bar,
+            file: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct WidgetConfig {
+    #[serde(rename = "type")]
+    kind: String,
+    name: Option<String>,
+    color: Option<String>,
+    background: Option<String>,
+    interval: Option<f64>,
+    signal: Option<u32>,
+    timeout: Option<f64>,
+    /// The options of the widget type
+    #[serde(flatten)]
+    options: toml::value::Table,
+}
+
+/// The widget with the common options of the configuration applied
+struct Configured<W: Widget + ?Sized> {
+    inner: Box<W>,
+    color: Option<ColorRGB>,
+    background: Option<ColorRGB>,
+    interval: Option<Duration>,
+}
+
+impl<W: Widget + ?Sized> Widget for Configured<W> {
+    fn try_update(&mut self) -> Result<Option<WidgetUpdate>> {
+        let mut update = self.inner.try_update()?;
+
+        if let Some(ref mut update) = update {
+            if let Some(interval) = self.interval {
+                update.refresh_interval = interval;
+            }
+
+            if let Some(ref mut data) = update.data {
+                for block in data.blocks_mut() {
+                    if let Some(ref color) = self.color {
+                        block.color(color.clone());
+                    }
+                    if let Some(ref background) = self.background {
+                        block.background(background.clone());
+                    }
+                }
+            }
+        }
+
+        Ok(update)
+    }
+
+    fn error_block(&self, error: &Error) -> Option<Block> {
+        self.inner.error_block(error)
+    }
+
+    fn on_click(&mut self, event: &ClickEvent) {
+        self.inner.on_click(event)
+    }
+
+    fn set_notifier(&mut self, notifier: Notifier) {
+        self.inner.set_notifier(notifier)
+    }
+}
+
+impl WidgetConfig {
+    /// Create the widget with the common options applied
+    fn build(&self, registry: &WidgetRegistry) -> Result<LayoutWidget> {
+        let timeout = match self.timeout {
+            Some(secs) if secs.is_finite() && secs > 0.0 => Some(Duration::from_secs_f64(secs)),
+            Some(secs) => return Err(Error::Parse(format!("Invalid timeout: {}", secs))),
+            None => None,
+        };
+
+        match registry.create(&self.kind, toml::Value::Table(self.options.clone()))? {
+            Created::Local(_) if timeout.is_some() => Err(Error::Parse(
+                "The timeout only applies to the widgets updated on their own thread".to_string(),
+            )),
+            Created::Local(inner) => Ok(LayoutWidget::Local(Box::new(self.configure(inner)?))),
+            Created::Threaded(inner, default_timeout) => Ok(LayoutWidget::Threaded(
+                Box::new(self.configure(inner)?),
+                timeout.unwrap_or(default_timeout),
+            )),
+        }
+    }
+
+    /// Apply the common options to the widget
+    fn configure<W: Widget + ?Sized>(&self, inner: Box<W>) -> Result<Configured<W>> {
+        let color = |color: &Option<String>| color.as_deref().map(ColorRGB::from_str).transpose();
+
+        let interval = match self.interval {
+            Some(secs) if secs.is_finite() && secs > 0.0 => Some(Duration::from_secs_f64(secs)),
+            Some(secs) => return Err(Error::Parse(format!("Invalid interval: {}", secs))),
+            None => None,
+        };
+
+        Ok(Configured {
+            inner,
+            color: color(&self.color)?,
+            background: color(&self.background)?,
+            interval,
+        })
+    }
+}
+
+/// The configuration file used when no path is given, which is
+/// `$XDG_CONFIG_HOME/i3monkit/config.toml`
+pub fn default_config_path() -> PathBuf {
+    let config_home = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => Path::new(&std::env::var_os("HOME").unwrap_or_default()).join(".config"),
+    };
+    config_home.join("i3monkit").join("config.toml")
+}
+
+impl FromStr for Config {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+}
+
+impl Config {
+    /// Read the configuration file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    /// The protocol header of the bar
+    pub fn header(&self) -> Header {
+        Header::new(1).click_events(self.bar.click_events)
+    }
+
+    /// The status bar the program writes to
+    pub fn output(&self) -> Output {
+        self.bar.output
+    }
+
+    /// Run the update loop of the bar, writing to the stdout in the format of the output
+    pub fn run(&self, bar: &mut WidgetCollection) {
+        self.start(bar, false)
+    }
+
+    /// Update the widgets once and draw the bar, see `WidgetCollection::draw_once`
+    ///
+    /// The widget collection is meant to be created by `build_once`.
+    pub fn run_once(&self, bar: &mut WidgetCollection) {
+        self.start(bar, true)
+    }
+
+    fn start(&self, bar: &mut WidgetCollection, once: bool) {
+        fn draw<O: BarOutput>(bar: &mut WidgetCollection, output: O, once: bool) {
+            if once {
+                bar.draw_once(output)
+            } else {
+                bar.update_loop(output)
+            }
+        }
+
+        let stdout = std::io::stdout();
+        match self.bar.output {
+            Output::I3bar => draw(bar, I3Protocol::new(self.header(), stdout), once),
+            Output::Lemonbar => {
+                let mut output = LemonbarOutput::new(stdout);
+                if self.bar.click_events && !once {
+                    let stdin = std::io::BufReader::new(std::io::stdin());
+                    output = output.clicks_from(stdin);
+                }
+                draw(bar, output, once)
+            }
+            Output::Polybar => {
+                let mut output = LemonbarOutput::polybar(stdout);
+                if self.bar.click_events && self.bar.ipc {
+                    let socket = match self.bar.socket {
+                        Some(ref path) => path.clone(),
+                        None => default_socket_path(),
+                    };
+                    let command = format!("i3monkit-msg -s '{}'", socket.display());
+                    output = output.click_command(&command);
+                }
+                draw(bar, output, once)
+            }
+            Output::Tmux => match self.bar.file {
+                Some(ref path) => draw(bar, TmuxOutput::file(path), once),
+                None => draw(bar, TmuxOutput::new(stdout), once),
+            },
+        }
+    }
+
+    /// Create the widget collection described by the configuration, with the builtin widgets
+    ///
+    /// The control server is started as well if it's enabled.
+    pub fn build(&self) -> Result<WidgetCollection> {
+        self.build_with(&WidgetRegistry::new())
+    }
+
+    /// Create the widget collection described by the configuration, with the widget types in the
+    /// registry
+    pub fn build_with(&self, registry: &WidgetRegistry) -> Result<WidgetCollection> {
+        let mut bar = self.build_once_with(registry)?;
+
+        if self.bar.ipc {
+            match self.bar.socket {
+                Some(ref path) => bar.enable_ipc_at(path)?,
+                None => bar.enable_ipc()?,
+            };
+        }
+
+        Ok(bar)
+    }
+
+    /// Create the widget collection described by the configuration to draw the bar once, see
+    /// `run_once`
+    ///
+    /// The control server is never started, so that the program drawing the bar once doesn't take
+    /// the socket of the bar that keeps running.
+    pub fn build_once(&self) -> Result<WidgetCollection> {
+        self.build_once_with(&WidgetRegistry::new())
+    }
+
+    /// Create the widget collection described by the configuration to draw the bar once, with the
+    /// widget types in the registry
+    pub fn build_once_with(&self, registry: &WidgetRegistry) -> Result<WidgetCollection> {
+        let mut bar = WidgetCollection::new();
+        bar.set_layout(self.layout(registry, &[])?);
+        Ok(bar)
+    }
+
+    /// Create the widget collection described by the configuration, which is reloaded when the
+    /// configuration file changes or the program receives SIGHUP
+    ///
+    /// Only the widgets are reloaded, the changes to the `bar` section take effect after restart.
+    /// The widgets whose configuration is unchanged are kept along with their state, while the
+    /// others are created again. If the new configuration is broken, the bar is kept as is.
+    ///
+    /// **path** The path to the configuration file
+    /// **registry** The widget types used by the configuration
+    pub fn build_watched<P: AsRef<Path>>(
+        &self,
+        path: P,
+        registry: WidgetRegistry,
+    ) -> Result<WidgetCollection> {
+        let mut bar = self.build_with(&registry)?;
+
+        let path = path.as_ref().to_path_buf();
+        let handle = bar.control_handle();
+        watch_file(&path, move || {
+            handle.reload();
+            true
+        })?;
+
+        bar.set_reloader(move |existing| Config::load(&path)?.layout(&registry, existing));
+
+        Ok(bar)
+    }
+
+    /// Build the widgets described by the configuration
+    ///
+    /// **existing** The keys of the existing widgets, which are kept instead of created again
+    fn layout(&self, registry: &WidgetRegistry, existing: &[String]) -> Result<Vec<LayoutEntry>> {
+        let mut existing: Vec<_> = existing.iter().collect();
+        let mut layout = Vec::new();
+
+        for config in self.widgets.iter() {
+            let signal = match config.signal {
+                Some(signal) => Some(
+                    realtime_signal(signal)
+                        .map_err(|_| Error::Parse(format!("No such signal: {}", signal)))?,
+                ),
+                None => None,
+            };
+
+            let key = serde_json::to_string(config).map_err(|e| Error::Parse(e.to_string()))?;
+
+            let widget = match existing.iter().position(|existing| **existing == key) {
+                Some(pos) => {
+                    existing.remove(pos);
+                    None
+                }
+                None => {
+                    let widget = config.build(registry).map_err(|e| match e {
+                        Error::Parse(what) => {
+                            Error::Parse(format!("{} widget: {}", config.kind, what))
+                        }
+                        e => e,
+                    })?;
+                    Some(widget)
+                }
+            };
+
+            layout.push(LayoutEntry {
+                key,
+                name: config.name.clone(),
+                signal,
+                widget,
+            });
+        }
+
+        Ok(layout)
+    }
+}
@@ -0,0 +1,209 @@
+//! The handle that changes the widgets of a running bar
+
+use crate::protocol::{Block, ClickEvent};
+use crate::widget::{Message, Widget};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A change to the widget set of a running bar
+pub(crate) enum Command {
+    /// Insert the widget with the name at the position
+    Insert(usize, String, Box<dyn Widget + Send>),
+    /// Remove the widget with the name
+    Remove(String),
+    /// Move the widget with the name to the position
+    Move(String, usize),
+    /// Swap the positions of the two widgets
+    Swap(String, String),
+    /// Replace the widget with the name by a new one
+    Replace(String, Box<dyn Widget + Send>),
+    /// Update the widget with the name right away
+    Refresh(String),
+    /// Click the first block of the widget with the button
+    Click(String, u32),
+    /// Click the block with the name and instance of the event
+    ClickBlock(ClickEvent),
+    /// Show the text in place of the widget for a while, or clear the text if None
+    Override(String, Option<(String, Option<Duration>)>),
+    /// Stop updating the bar
+    Pause,
+    /// Resume updating the bar
+    Resume,
+    /// Build the widgets from the configuration again
+    Reload,
+    /// Report the name and the blocks of every widget
+    List(Sender<Vec<(String, Vec<Block>)>>),
+}
+
+/// The handle that adds, removes and reorders the widgets while the bar is running
+///
+/// The handle can be cloned and sent to any thread. The changes are applied by the update loop,
+/// and the bar is redrawn right after that.
+///
+/// The widgets are referred to by name, see `WidgetCollection::push_named`. A widget pushed without
+/// a name is named after the order it's pushed, starting from "0". If multiple widgets share a
+/// name, the first one on the bar is used. A change to a widget that doesn't exist is ignored.
+///
+/// ```no_run
+/// use i3monkit::widgets::{DateTimeWidget, NetworkSpeedWidget};
+/// use i3monkit::{Header, I3Protocol, WidgetCollection};
+///
+/// let mut bar = WidgetCollection::new();
+/// bar.push_named("time", DateTimeWidget::new());
+///
+/// let handle = bar.control_handle();
+/// std::thread::spawn(move || {
+///     // Show the VPN traffic once the tunnel is up
+///     while !std::path::Path::new("/sys/class/net/tun0").exists() {
+///         std::thread::sleep(std::time::Duration::from_secs(5));
+///     }
+///     handle.insert(0, "vpn", NetworkSpeedWidget::new("tun0"));
+/// });
+///
+/// bar.update_loop(I3Protocol::new(Header::new(1), std::io::stdout()));
+/// ```
+#[derive(Clone)]
+pub struct ControlHandle {
+    message_tx: Sender<Message>,
+    _alive: Arc<()>,
+}
+
+impl ControlHandle {
+    pub(crate) fn new(message_tx: Sender<Message>, alive: Arc<()>) -> Self {
+        ControlHandle {
+            message_tx,
+            _alive: alive,
+        }
+    }
+
+    fn send(&self, command: Command) {
+        self.message_tx.send(Message::Control(command)).ok();
+    }
+
+    /// Insert a new widget to the bar
+    ///
+    /// **position** The position of the widget, starting from the left most one. A position past
+    /// the last widget appends the widget to the bar
+    /// **name** The name of the widget
+    /// **widget** The widget to insert
+    pub fn insert<W: Widget + Send + 'static>(&self, position: usize, name: &str, widget: W) {
+        self.send(Command::Insert(
+            position,
+            name.to_string(),
+            Box::new(widget),
+        ));
+    }
+
+    /// Append a new widget to the bar
+    ///
+    /// **name** The name of the widget
+    /// **widget** The widget to append
+    pub fn push<W: Widget + Send + 'static>(&self, name: &str, widget: W) {
+        self.insert(usize::MAX, name, widget);
+    }
+
+    /// Remove a widget from the bar
+    pub fn remove(&self, name: &str) {
+        self.send(Command::Remove(name.to_string()));
+    }
+
+    /// Move a widget to another position
+    ///
+    /// **name** The name of the widget
+    /// **position** The new position of the widget, counted after the widget is taken off the bar
+    pub fn move_to(&self, name: &str, position: usize) {
+        self.send(Command::Move(name.to_string(), position));
+    }
+
+    /// Swap the positions of two widgets
+    pub fn swap(&self, first: &str, second: &str) {
+        self.send(Command::Swap(first.to_string(), second.to_string()));
+    }
+
+    /// Replace a widget with a new one
+    ///
+    /// The new widget takes the position, the name and the refresh signal of the old one.
+    ///
+    /// **name** The name of the widget to replace
+    /// **widget** The new widget
+    pub fn replace<W: Widget + Send + 'static>(&self, name: &str, widget: W) {
+        self.send(Command::Replace(name.to_string(), Box::new(widget)));
+    }
+
+    /// Update a widget right away, without waiting for its refresh interval
+    pub fn refresh(&self, name: &str) {
+        self.send(Command::Refresh(name.to_string()));
+    }
+
+    /// Click the first block of a widget, as if the user clicked it on the bar
+    ///
+    /// **name** The name of the widget
+    /// **button** The mouse button, 1 for the left button, 2 for the middle, 3 for the right
+    pub fn click(&self, name: &str, button: u32) {
+        self.send(Command::Click(name.to_string(), button));
+    }
+
+    /// Click a block, as if the user clicked it on the bar
+    ///
+    /// The click goes to the widget that draws the block with the name and instance, just like a
+    /// click reported by the bar.
+    ///
+    /// **name** The name of the block
+    /// **instance** The instance of the block
+    /// **button** The mouse button, 1 for the left button, 2 for the middle, 3 for the right
+    pub fn click_block(&self, name: &str, instance: &str, button: u32) {
+        self.send(Command::ClickBlock(ClickEvent {
+            name: name.to_string(),
+            instance: instance.to_string(),
+            button,
+            ..Default::default()
+        }));
+    }
+
+    /// Show a text in place of a widget
+    ///
+    /// The widget keeps being updated, but its blocks are hidden behind the text until the
+    /// duration expires or the override is cleared.
+    ///
+    /// **name** The name of the widget
+    /// **text** The text to show
+    /// **duration** How long the text is shown, None to show it until cleared
+    pub fn set_override(&self, name: &str, text: &str, duration: Option<Duration>) {
+        let text = Some((text.to_string(), duration));
+        self.send(Command::Override(name.to_string(), text));
+    }
+
+    /// Show the blocks of a widget again after `set_override`
+    pub fn clear_override(&self, name: &str) {
+        self.send(Command::Override(name.to_string(), None));
+    }
+
+    /// Stop updating the bar, as if the bar is hidden by i3bar
+    pub fn pause(&self) {
+        self.send(Command::Pause);
+    }
+
+    /// Resume updating the bar, every widget is updated right away
+    pub fn resume(&self) {
+        self.send(Command::Resume);
+    }
+
+    /// Reload the configuration the bar is built from
+    ///
+    /// This only works for a bar built with `Config::build_watched`, and does the same as sending
+    /// SIGHUP to the program.
+    pub fn reload(&self) {
+        self.send(Command::Reload);
+    }
+
+    /// Get the name and the blocks currently shown of every widget, in the order on the bar
+    ///
+    /// This waits for the update loop to answer, so it shouldn't be called from a widget updated
+    /// on the main thread.
+    pub fn widgets(&self) -> Vec<(String, Vec<Block>)> {
+        let (reply_tx, reply_rx) = channel();
+        self.send(Command::List(reply_tx));
+        reply_rx.recv().unwrap_or_default()
+    }
+}
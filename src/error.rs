@@ -0,0 +1,70 @@
+//! The error type of i3monkit
+
+use std::fmt::{Display, Formatter};
+
+/// The error reported by a widget update
+#[derive(Debug)]
+pub enum Error {
+    /// An I/O error, for example, failed to read a file under `/sys`
+    Io(std::io::Error),
+    /// The data read by the widget can not be parsed
+    Parse(String),
+    /// The widget panicked during the update
+    Panic(String),
+    /// Any other error reported by the widget
+    Other(String),
+}
+
+/// The result type of i3monkit
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Parse(what) => write!(f, "Parse error: {}", what),
+            Error::Panic(what) => write!(f, "Widget panicked: {}", what),
+            Error::Other(what) => write!(f, "{}", what),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Error {
+    /// Create the error from the payload of a panic
+    pub(crate) fn from_panic(payload: Box<dyn std::any::Any + Send>) -> Self {
+        let what = if let Some(what) = payload.downcast_ref::<&str>() {
+            what.to_string()
+        } else if let Some(what) = payload.downcast_ref::<String>() {
+            what.clone()
+        } else {
+            "unknown".to_string()
+        };
+        Error::Panic(what)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<std::num::ParseIntError> for Error {
+    fn from(e: std::num::ParseIntError) -> Self {
+        Error::Parse(e.to_string())
+    }
+}
+
+impl From<std::num::ParseFloatError> for Error {
+    fn from(e: std::num::ParseFloatError) -> Self {
+        Error::Parse(e.to_string())
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(e: toml::de::Error) -> Self {
+        Error::Parse(e.to_string())
+    }
+}
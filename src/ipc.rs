@@ -0,0 +1,211 @@
+//! The control server that drives a running bar through a UNIX socket
+//!
+//! The server reads one command per line and answers each of them with one line, which is either
+//! `ok`, `error: <reason>`, or the JSON output of the `list` command.
+//!
+//! The commands are:
+//!
+//! - `list` Show every widget with the blocks it currently draws
+//! - `refresh <widget>` Update the widget right away
+//! - `click <widget> [button]` Click the first block of the widget, with the left button by default
+//! - `click-block <button> <name> [instance]` Click the block with the name and instance, which
+//!   are percent-encoded
+//! - `override <widget> <seconds> <text>` Show the text in place of the widget, 0 second means
+//!   until cleared
+//! - `clear <widget>` Show the widget again after `override`
+//! - `pause` Stop updating the bar
+//! - `resume` Resume updating the bar
+//! - `reload` Reload the configuration file, see `Config::build_watched`
+//!
+//! The `i3monkit-msg` program that comes with this crate sends the commands from the shell.
+
+use crate::control::ControlHandle;
+use crate::protocol::Block;
+
+use serde::Serialize;
+
+use std::io::{BufRead, BufReader, ErrorKind, Read, Result, Write};
+use std::net::Shutdown;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::str::SplitWhitespace;
+use std::time::Duration;
+
+/// The socket path used when no path is given, which is `$XDG_RUNTIME_DIR/i3monkit.sock`
+pub fn default_socket_path() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => Path::new(&dir).join("i3monkit.sock"),
+        None => PathBuf::from(format!("/tmp/i3monkit-{}.sock", unsafe { libc::getuid() })),
+    }
+}
+
+/// Percent-encode the text, so that it's passed as a single word of a command
+pub(crate) fn encode(text: &str) -> String {
+    let mut result = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' => {
+                result.push(byte as char)
+            }
+            _ => result.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    result
+}
+
+/// Decode the percent-encoded text, None if it's not properly encoded
+pub(crate) fn decode(text: &str) -> Option<String> {
+    let mut bytes = Vec::new();
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Send a command to the bar listening on the socket and returns the answer
+///
+/// **path** The path to the socket
+/// **command** The command line, for example, `refresh volume`
+pub fn request<P: AsRef<Path>>(path: P, command: &str) -> Result<String> {
+    let mut stream = UnixStream::connect(path)?;
+    writeln!(stream, "{}", command)?;
+    stream.shutdown(Shutdown::Write)?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    Ok(response.trim_end().to_string())
+}
+
+/// The running control server, the socket file is removed when it's dropped
+pub(crate) struct Server {
+    path: PathBuf,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.path).ok();
+    }
+}
+
+/// Listen on the socket and apply the commands through the control handle
+///
+/// A socket left behind by a bar that is no longer running is replaced.
+pub(crate) fn serve(path: &Path, handle: ControlHandle) -> Result<Server> {
+    let listener = match UnixListener::bind(path) {
+        Ok(listener) => listener,
+        Err(e) if e.kind() == ErrorKind::AddrInUse => {
+            if UnixStream::connect(path).is_ok() {
+                return Err(std::io::Error::new(
+                    ErrorKind::AddrInUse,
+                    "Another bar is listening on the socket",
+                ));
+            }
+            std::fs::remove_file(path)?;
+            UnixListener::bind(path)?
+        }
+        Err(e) => return Err(e),
+    };
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let handle = handle.clone();
+            std::thread::spawn(move || serve_client(stream, &handle).ok());
+        }
+    });
+
+    Ok(Server {
+        path: path.to_path_buf(),
+    })
+}
+
+fn serve_client(stream: UnixStream, handle: &ControlHandle) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let response = match execute(&line?, handle) {
+            Ok(response) => response,
+            Err(reason) => format!("error: {}", reason),
+        };
+        writeln!(writer, "{}", response)?;
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct WidgetInfo<'a> {
+    name: &'a str,
+    blocks: &'a [Block],
+}
+
+/// Take the widget name from the command and make sure the widget exists
+fn widget_name<'a>(
+    args: &mut SplitWhitespace<'a>,
+    handle: &ControlHandle,
+) -> std::result::Result<&'a str, String> {
+    let name = args.next().ok_or("Missing widget name")?;
+    if handle.widgets().iter().any(|(widget, _)| widget == name) {
+        Ok(name)
+    } else {
+        Err(format!("No such widget: {}", name))
+    }
+}
+
+fn execute(line: &str, handle: &ControlHandle) -> std::result::Result<String, String> {
+    let mut args = line.split_whitespace();
+
+    match args.next().unwrap_or("") {
+        "list" => {
+            let widgets = handle.widgets();
+            let info: Vec<_> = widgets
+                .iter()
+                .map(|(name, blocks)| WidgetInfo { name, blocks })
+                .collect();
+            return serde_json::to_string(&info).map_err(|e| e.to_string());
+        }
+        "refresh" => handle.refresh(widget_name(&mut args, handle)?),
+        "click" => {
+            let name = widget_name(&mut args, handle)?;
+            let button = match args.next() {
+                Some(button) => button.parse().map_err(|_| "Invalid button")?,
+                None => 1,
+            };
+            handle.click(name, button);
+        }
+        "click-block" => {
+            let button = args
+                .next()
+                .ok_or("Missing button")?
+                .parse()
+                .map_err(|_| "Invalid button")?;
+            let name = decode(args.next().ok_or("Missing block name")?).ok_or("Invalid name")?;
+            let instance = decode(args.next().unwrap_or("")).ok_or("Invalid instance")?;
+            handle.click_block(&name, &instance, button);
+        }
+        "override" => {
+            let name = widget_name(&mut args, handle)?;
+            let seconds: u64 = args
+                .next()
+                .ok_or("Missing duration")?
+                .parse()
+                .map_err(|_| "Invalid duration")?;
+            let text = args.collect::<Vec<_>>().join(" ");
+            let duration = Some(Duration::from_secs(seconds)).filter(|d| *d > Duration::new(0, 0));
+            handle.set_override(name, &text, duration);
+        }
+        "clear" => handle.clear_override(widget_name(&mut args, handle)?),
+        "pause" => handle.pause(),
+        "resume" => handle.resume(),
+        "reload" => handle.reload(),
+        "" => return Err("Empty command".to_string()),
+        command => return Err(format!("Unknown command: {}", command)),
+    }
+
+    Ok("ok".to_string())
+}
@@ -89,7 +89,8 @@
 //!     fn update(&mut self) -> Option<WidgetUpdate> {
 //!         Some(WidgetUpdate{
 //!             refresh_interval: std::time::Duration::new(3600,0),
-//!             data: Some(Block::new().append_full_text(self.0).clone())
+//!             aligned: false,
+//!             data: Some(Block::new().append_full_text(self.0).clone().into())
 //!         })
 //!     }
 //! }
@@ -101,9 +102,29 @@
 //! }
 //! ```
 //!
+mod clock;
+pub mod config;
+mod control;
+mod error;
+pub mod ipc;
+mod output;
+pub mod outputs;
 mod protocol;
+mod registry;
+mod signal;
+mod watch;
 mod widget;
 pub mod widgets;
+mod worker;
 
-pub use crate::protocol::{Block, ColorRGB, Header, I3Protocol};
-pub use crate::widget::{Decoratable, Widget, WidgetCollection, WidgetUpdate};
+pub use crate::control::ControlHandle;
+pub use crate::error::{Error, Result};
+pub use crate::output::{BarOutput, ClickSender};
+pub use crate::protocol::{
+    Align, Block, ClickEvent, ClickEventReader, ColorRGB, Header, I3Protocol, I3ProtocolReader,
+    MarkupLang, MinWidth,
+};
+pub use crate::registry::WidgetRegistry;
+pub use crate::widget::{
+    Decoratable, Notifier, Widget, WidgetCollection, WidgetData, WidgetUpdate,
+};
@@ -0,0 +1,104 @@
+//! Where the update loop draws the bar
+
+use crate::protocol::{Block, ClickEvent, ClickEventReader, I3Protocol};
+use crate::widget::Message;
+
+use std::io::Write;
+use std::sync::mpsc::Sender;
+
+/// The output the update loop draws the blocks to
+///
+/// `I3Protocol` draws the bar for i3bar and swaybar, and other status bars can reuse every widget
+/// by implementing this trait. A function taking the blocks is an output as well, which is handy
+/// to write the bar somewhere else, or to record it:
+///
+/// ```no_run
+/// use i3monkit::widgets::DateTimeWidget;
+/// use i3monkit::{Block, WidgetCollection};
+/// use std::process::Command;
+///
+/// // Show the bar as the name of the root window, which is what dwm shows
+/// let mut bar = WidgetCollection::new();
+/// bar.push(DateTimeWidget::new());
+/// bar.update_loop(|blocks: &[Block]| {
+///     let text: Vec<_> = blocks.iter().map(|block| block.get_full_text()).collect();
+///     Command::new("xsetroot").arg("-name").arg(text.join(" | ")).status().ok();
+/// });
+/// ```
+pub trait BarOutput {
+    /// Draw the blocks, which is called every time the blocks change
+    ///
+    /// **blocks** The blocks of all the widgets, from the left to the right
+    fn refresh(&mut self, blocks: &[Block]);
+
+    /// Start delivering the clicks on the blocks to the update loop
+    ///
+    /// This is called once before the update loop starts. An output that reports the clicks
+    /// keeps the sender, usually on a thread reading the clicks, and sends each click through it.
+    fn start_clicks(&mut self, _clicks: ClickSender) {}
+
+    /// The signal sent to the program when the bar is hidden, which pauses the update loop
+    fn stop_signal(&self) -> u32 {
+        libc::SIGSTOP as u32
+    }
+
+    /// The signal sent to the program when the bar is shown again, which resumes the update loop
+    fn cont_signal(&self) -> u32 {
+        libc::SIGCONT as u32
+    }
+}
+
+/// The sender passing the clicks on the blocks to the update loop
+///
+/// The widget whose block has the name and instance of the click event is clicked.
+#[derive(Clone)]
+pub struct ClickSender {
+    message_tx: Sender<Message>,
+}
+
+impl ClickSender {
+    pub(crate) fn new(message_tx: Sender<Message>) -> Self {
+        ClickSender { message_tx }
+    }
+
+    /// Send the click to the update loop, returns false if the loop has exited
+    pub fn send(&self, event: ClickEvent) -> bool {
+        self.message_tx.send(Message::Click(event)).is_ok()
+    }
+}
+
+impl<T: Write> BarOutput for I3Protocol<T> {
+    fn refresh(&mut self, blocks: &[Block]) {
+        I3Protocol::refresh(self, blocks)
+    }
+
+    /// Read the click events i3bar writes to the stdin, if they are enabled in the header
+    fn start_clicks(&mut self, clicks: ClickSender) {
+        if !self.click_events_enabled() {
+            return;
+        }
+
+        std::thread::spawn(move || {
+            let stdin = std::io::stdin();
+            for event in ClickEventReader::new(stdin.lock()) {
+                if !clicks.send(event) {
+                    break;
+                }
+            }
+        });
+    }
+
+    fn stop_signal(&self) -> u32 {
+        I3Protocol::stop_signal(self)
+    }
+
+    fn cont_signal(&self) -> u32 {
+        I3Protocol::cont_signal(self)
+    }
+}
+
+impl<F: FnMut(&[Block])> BarOutput for F {
+    fn refresh(&mut self, blocks: &[Block]) {
+        self(blocks)
+    }
+}
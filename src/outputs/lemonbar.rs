@@ -0,0 +1,346 @@
+use crate::ipc::{decode, encode};
+use crate::output::{BarOutput, ClickSender};
+use crate::outputs::markup::{segments, Style};
+use crate::protocol::{Block, ClickEvent, ColorRGB};
+
+use std::io::{BufRead, BufReader, Result, Write};
+use std::process::{ChildStdin, Command, Stdio};
+
+/// The mouse buttons a block reacts to
+const BUTTONS: [u32; 5] = [1, 2, 3, 4, 5];
+
+/// The output that draws the bar for lemonbar, or for polybar
+///
+/// The colors of the blocks, as well as the Pango markup used by the builtin widgets, are
+/// translated to the `%{F#rrggbb}`, `%{B#rrggbb}` and `%{+u}` formatting tags, and the urgent
+/// blocks are drawn with the colors reversed. The blocks are aligned to the right in lemonbar and
+/// separated by ` | ` unless another separator is given.
+///
+/// The clicks on a block are handled by wrapping the block in `%{A}` tags:
+///
+/// - lemonbar prints the action of the clicked area to its stdout, which is
+///   `click/BUTTON/NAME/INSTANCE` with the name and instance of the block percent-encoded. The
+///   clicks are read back from the output of lemonbar given to `clicks_from`, or from lemonbar
+///   itself when it's started by `spawn`.
+/// - polybar runs the action as a shell command, which is the command given to `click_command`
+///   followed by `click-block BUTTON NAME INSTANCE`, with the name and instance percent-encoded.
+///   This is meant for `i3monkit-msg` and the control server of the bar, see
+///   `WidgetCollection::enable_ipc`. The click goes to the widget that draws the block, like a
+///   click on i3bar.
+///
+/// ```no_run
+/// use i3monkit::outputs::LemonbarOutput;
+/// use i3monkit::widgets::{BatteryWidget, CpuWidget};
+/// use i3monkit::WidgetCollection;
+///
+/// let mut bar = WidgetCollection::new();
+/// bar.push(CpuWidget::new(0));
+/// bar.push(BatteryWidget::new(0));
+/// bar.update_loop(LemonbarOutput::spawn("lemonbar -p -g x20").unwrap());
+/// ```
+pub struct LemonbarOutput<T: Write> {
+    writer: T,
+    polybar: bool,
+    separator: String,
+    clicks: Option<Box<dyn BufRead + Send>>,
+    /// If lemonbar reports the clicks to the bar
+    clickable: bool,
+    click_command: Option<String>,
+}
+
+impl LemonbarOutput<ChildStdin> {
+    /// Start lemonbar and draw the bar on it, the clicks are read from the output of lemonbar
+    ///
+    /// **command** The shell command starting lemonbar, such as `lemonbar -p`
+    pub fn spawn(command: &str) -> Result<Self> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Ok(LemonbarOutput::new(child.stdin.take().unwrap()).clicks_from(stdout))
+    }
+}
+
+impl<T: Write> LemonbarOutput<T> {
+    /// Create an output in the lemonbar format
+    ///
+    /// **writer** Where the bar should be written, which is read by lemonbar
+    pub fn new(writer: T) -> Self {
+        LemonbarOutput {
+            writer,
+            polybar: false,
+            separator: " | ".to_string(),
+            clicks: None,
+            clickable: false,
+            click_command: None,
+        }
+    }
+
+    /// Create an output in the polybar format, for a `custom/script` module with `tail = true`
+    ///
+    /// **writer** Where the bar should be written, normally the stdout
+    pub fn polybar(writer: T) -> Self {
+        LemonbarOutput {
+            polybar: true,
+            ..Self::new(writer)
+        }
+    }
+
+    /// Set the text drawn between the blocks
+    pub fn separator(mut self, separator: &str) -> Self {
+        self.separator = separator.to_string();
+        self
+    }
+
+    /// Read the actions lemonbar prints for the clicked areas
+    ///
+    /// **input** The output of lemonbar
+    pub fn clicks_from<R: BufRead + Send + 'static>(mut self, input: R) -> Self {
+        self.clicks = Some(Box::new(input));
+        self.clickable = true;
+        self
+    }
+
+    /// Set the command polybar runs when a block is clicked, such as `i3monkit-msg -s SOCKET`
+    pub fn click_command(mut self, command: &str) -> Self {
+        self.click_command = Some(command.to_string());
+        self
+    }
+
+    /// The action of the clickable area of the block, if the clicks are handled
+    fn action(&self, block: &Block, button: u32) -> Option<String> {
+        let action = if self.polybar {
+            let mut action = format!(
+                "{} click-block {} {}",
+                self.click_command.as_ref()?,
+                button,
+                encode(block.get_name())
+            );
+            if !block.get_instance().is_empty() {
+                action.push(' ');
+                action.push_str(&encode(block.get_instance()));
+            }
+            action
+        } else if self.clickable {
+            format!(
+                "click/{}/{}/{}",
+                button,
+                encode(block.get_name()),
+                encode(block.get_instance())
+            )
+        } else {
+            return None;
+        };
+
+        Some(action.replace(':', "\\:"))
+    }
+
+    /// Format a block, the formatting is reset at the end of the block
+    fn format_block(&self, block: &Block, out: &mut String) {
+        let actions: Vec<_> = BUTTONS
+            .iter()
+            .filter_map(|button| Some((button, self.action(block, *button)?)))
+            .collect();
+        for (button, action) in actions.iter() {
+            out.push_str(&format!("%{{A{}:{}:}}", button, action));
+        }
+
+        if block.is_urgent() {
+            out.push_str("%{R}");
+        }
+
+        let mut current = Style::default();
+        for segment in segments(block) {
+            let style = segment.style;
+            if style.foreground != current.foreground {
+                out.push_str(&format!("%{{F{}}}", color(&style.foreground)));
+            }
+            if style.background != current.background {
+                out.push_str(&format!("%{{B{}}}", color(&style.background)));
+            }
+            if style.underline != current.underline {
+                out.push_str(if style.underline { "%{+u}" } else { "%{-u}" });
+            }
+            out.push_str(&segment.text.replace('%', "%%"));
+            current = style;
+        }
+
+        if current.foreground.is_some() {
+            out.push_str("%{F-}");
+        }
+        if current.background.is_some() {
+            out.push_str("%{B-}");
+        }
+        if current.underline {
+            out.push_str("%{-u}");
+        }
+        if block.is_urgent() {
+            out.push_str("%{R}");
+        }
+
+        for _ in actions.iter() {
+            out.push_str("%{A}");
+        }
+    }
+}
+
+/// The color in the formatting tags, `-` being the default color
+fn color(color: &Option<ColorRGB>) -> String {
+    match color {
+        Some(ColorRGB(r, g, b)) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        None => "-".to_string(),
+    }
+}
+
+/// Percent-encode the text, so that it doesn't contain any character lemonbar treats specially
+/// Parse the action lemonbar prints when a block is clicked
+fn parse_action(action: &str) -> Option<ClickEvent> {
+    let mut fields = action.trim().strip_prefix("click/")?.split('/');
+    let button = fields.next()?.parse().ok()?;
+    let name = decode(fields.next()?)?;
+    let instance = decode(fields.next()?)?;
+
+    Some(ClickEvent {
+        name,
+        instance,
+        button,
+        ..Default::default()
+    })
+}
+
+impl<T: Write> BarOutput for LemonbarOutput<T> {
+    fn refresh(&mut self, blocks: &[Block]) {
+        let mut line = String::new();
+        if !self.polybar {
+            line.push_str("%{r}");
+        }
+
+        for (idx, block) in blocks.iter().enumerate() {
+            if idx > 0 {
+                line.push_str(&self.separator.replace('%', "%%"));
+            }
+            self.format_block(block, &mut line);
+        }
+
+        writeln!(self.writer, "{}", line).expect("Cannot write");
+        self.writer.flush().ok();
+    }
+
+    /// Read the click events from the actions lemonbar prints
+    fn start_clicks(&mut self, clicks: ClickSender) {
+        let input = match self.clicks.take() {
+            Some(input) => input,
+            None => return,
+        };
+
+        std::thread::spawn(move || {
+            for line in input.lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+
+                if let Some(event) = parse_action(&line) {
+                    if !clicks.send(event) {
+                        break;
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw(mut output: LemonbarOutput<Vec<u8>>, blocks: &[Block]) -> String {
+        output.refresh(blocks);
+        String::from_utf8(output.writer).unwrap()
+    }
+
+    #[test]
+    fn percent_encoding_round_trip() {
+        for text in &["", "cpu", "a/b:c}d%e f", "naïve ✓", "%41"] {
+            let encoded = encode(text);
+            assert!(encoded
+                .bytes()
+                .all(|byte| byte.is_ascii_alphanumeric() || b"-_.%".contains(&byte)));
+            assert_eq!(decode(&encoded).as_deref(), Some(*text));
+        }
+
+        assert_eq!(decode("%4"), None);
+        assert_eq!(decode("%zz"), None);
+        assert_eq!(decode("%ff"), None);
+    }
+
+    #[test]
+    fn clicks_are_read_back_from_the_action() {
+        let output = LemonbarOutput::new(Vec::new()).clicks_from(std::io::empty());
+        let mut block = Block::new();
+        block.name("disk/usage").instance("/home: 50%");
+
+        let event = parse_action(&output.action(&block, 3).unwrap()).unwrap();
+        assert_eq!(event.name, "disk/usage");
+        assert_eq!(event.instance, "/home: 50%");
+        assert_eq!(event.button, 3);
+
+        assert!(parse_action("click/1/cpu").is_none());
+        assert!(parse_action("click/x/cpu/0").is_none());
+        assert!(parse_action("something else").is_none());
+    }
+
+    #[test]
+    fn no_actions_without_clicks() {
+        let mut block = Block::new();
+        block.name("cpu").full_text("50%");
+        assert_eq!(
+            draw(LemonbarOutput::new(Vec::new()), &[block]),
+            "%{r}50%%\n"
+        );
+    }
+
+    #[test]
+    fn polybar_actions_run_the_click_command() {
+        let output = LemonbarOutput::polybar(Vec::new()).click_command("msg -s /tmp/a:b");
+        let mut block = Block::new();
+        block.name("it's");
+        assert_eq!(
+            output.action(&block, 1).unwrap(),
+            "msg -s /tmp/a\\:b click-block 1 it%27s"
+        );
+        block.instance("/home: 50%");
+        assert_eq!(
+            output.action(&block, 3).unwrap(),
+            "msg -s /tmp/a\\:b click-block 3 it%27s %2Fhome%3A%2050%25"
+        );
+        assert!(LemonbarOutput::polybar(Vec::new())
+            .action(&block, 1)
+            .is_none());
+    }
+
+    #[test]
+    fn formatting() {
+        let mut urgent = Block::new();
+        urgent
+            .full_text("<span foreground=\"#f00\">hot</span> <u>now</u>")
+            .background(ColorRGB(0, 0, 0))
+            .urgent(true)
+            .use_pango();
+        let mut plain = Block::new();
+        plain.full_text("100%");
+
+        let line = draw(
+            LemonbarOutput::new(Vec::new()).separator(" % "),
+            &[urgent, plain],
+        );
+        assert_eq!(
+            line,
+            "%{r}%{R}%{F#ff0000}%{B#000000}hot%{F-} %{+u}now%{B-}%{-u}%{R} %% 100%%\n"
+        );
+    }
+}
@@ -0,0 +1,302 @@
+use crate::protocol::{Block, ColorRGB, MarkupLang};
+
+use std::str::FromStr;
+
+/// The style of a piece of text
+#[derive(Clone, Default, PartialEq)]
+pub(crate) struct Style {
+    pub(crate) foreground: Option<ColorRGB>,
+    pub(crate) background: Option<ColorRGB>,
+    pub(crate) bold: bool,
+    pub(crate) italic: bool,
+    pub(crate) underline: bool,
+}
+
+/// A piece of text of a block drawn in a single style
+pub(crate) struct Segment {
+    pub(crate) style: Style,
+    pub(crate) text: String,
+}
+
+/// The colors Pango knows by name that the builtin widgets use, along with the other basic ones
+const NAMED_COLORS: &[(&str, ColorRGB)] = &[
+    ("black", ColorRGB(0x00, 0x00, 0x00)),
+    ("white", ColorRGB(0xff, 0xff, 0xff)),
+    ("red", ColorRGB(0xff, 0x00, 0x00)),
+    ("green", ColorRGB(0x00, 0xff, 0x00)),
+    ("blue", ColorRGB(0x00, 0x00, 0xff)),
+    ("yellow", ColorRGB(0xff, 0xff, 0x00)),
+    ("cyan", ColorRGB(0x00, 0xff, 0xff)),
+    ("magenta", ColorRGB(0xff, 0x00, 0xff)),
+    ("orange", ColorRGB(0xff, 0xa5, 0x00)),
+    ("purple", ColorRGB(0xa0, 0x20, 0xf0)),
+    ("grey", ColorRGB(0xbe, 0xbe, 0xbe)),
+    ("gray", ColorRGB(0xbe, 0xbe, 0xbe)),
+];
+
+/// Parse a Pango color, which is either `#rgb`, `#rrggbb` or a color name
+fn parse_color(text: &str) -> Option<ColorRGB> {
+    let text = text.trim();
+    match text.strip_prefix('#') {
+        Some(hex) if hex.len() == 3 && hex.is_ascii() => {
+            let channel = |pos: usize| u8::from_str_radix(&hex[pos..pos + 1], 16).ok();
+            Some(ColorRGB(
+                channel(0)? * 0x11,
+                channel(1)? * 0x11,
+                channel(2)? * 0x11,
+            ))
+        }
+        Some(_) => ColorRGB::from_str(text).ok(),
+        None => NAMED_COLORS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(text))
+            .map(|(_, color)| color.clone()),
+    }
+}
+
+/// Replace the XML entities with the characters
+fn unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest.find(';').map(|end| (&rest[1..end], end));
+        let decoded = entity.and_then(|(name, _)| match name {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match name.strip_prefix("#x") {
+                Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                None => name
+                    .strip_prefix('#')?
+                    .parse()
+                    .ok()
+                    .and_then(char::from_u32),
+            },
+        });
+
+        match (decoded, entity) {
+            (Some(c), Some((_, end))) => {
+                result.push(c);
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// The attributes of a tag, such as `foreground="red"`
+fn attributes(mut text: &str) -> Vec<(&str, &str)> {
+    let mut result = Vec::new();
+    while let Some(eq) = text.find('=') {
+        let name = text[..eq].trim();
+        let value = text[eq + 1..].trim_start();
+        let quote = match value.chars().next() {
+            Some(quote) if quote == '"' || quote == '\'' => quote,
+            _ => break,
+        };
+        let value = &value[1..];
+        let end = match value.find(quote) {
+            Some(end) => end,
+            None => break,
+        };
+        result.push((name, &value[..end]));
+        text = &value[end + 1..];
+    }
+    result
+}
+
+/// The style of the text inside the tag
+fn apply_tag(tag: &str, mut style: Style) -> Style {
+    let (name, attrs) = match tag.find(char::is_whitespace) {
+        Some(pos) => (&tag[..pos], &tag[pos..]),
+        None => (tag, ""),
+    };
+
+    match name {
+        "b" => style.bold = true,
+        "i" => style.italic = true,
+        "u" => style.underline = true,
+        "span" => {
+            for (attr, value) in attributes(attrs) {
+                match attr {
+                    "foreground" | "fgcolor" | "color" => {
+                        style.foreground = parse_color(value).or(style.foreground)
+                    }
+                    "background" | "bgcolor" => {
+                        style.background = parse_color(value).or(style.background)
+                    }
+                    "weight" | "font_weight" => style.bold = value == "bold" || value == "heavy",
+                    "style" | "font_style" => style.italic = value == "italic",
+                    "underline" => style.underline = value != "none",
+                    _ => (),
+                }
+            }
+        }
+        _ => (),
+    }
+
+    style
+}
+
+/// Split the Pango markup into the pieces of text of the same style
+fn parse_pango(text: &str, base: Style) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut stack = vec![base];
+    let mut rest = text;
+
+    loop {
+        let (content, tag) = match rest.find('<') {
+            Some(start) => match rest[start..].find('>') {
+                Some(len) => (&rest[..start], Some(&rest[start + 1..start + len])),
+                // Not a tag after all
+                None => (rest, None),
+            },
+            None => (rest, None),
+        };
+
+        if !content.is_empty() {
+            segments.push(Segment {
+                style: stack.last().cloned().unwrap_or_default(),
+                text: unescape(content),
+            });
+        }
+
+        let tag = match tag {
+            Some(tag) => tag,
+            None => break,
+        };
+        rest = &rest[content.len() + tag.len() + 2..];
+
+        if tag.starts_with('/') {
+            if stack.len() > 1 {
+                stack.pop();
+            }
+        } else if !tag.ends_with('/') {
+            let style = apply_tag(tag.trim(), stack.last().cloned().unwrap_or_default());
+            stack.push(style);
+        }
+    }
+
+    segments
+}
+
+/// Split the text of the block into the pieces of text of the same style
+///
+/// The colors of the block are the default colors of the text, which the Pango markup overrides.
+pub(crate) fn segments(block: &Block) -> Vec<Segment> {
+    let base = Style {
+        foreground: block.get_color().cloned(),
+        background: block.get_background().cloned(),
+        ..Style::default()
+    };
+
+    match block.get_markup() {
+        MarkupLang::Pango => parse_pango(block.get_full_text(), base),
+        MarkupLang::Text => vec![Segment {
+            style: base,
+            text: block.get_full_text().to_string(),
+        }],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(segments: &[Segment]) -> Vec<&str> {
+        segments
+            .iter()
+            .map(|segment| segment.text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(parse_color("#abc"), Some(ColorRGB(0xaa, 0xbb, 0xcc)));
+        assert_eq!(parse_color("#a0b1c2"), Some(ColorRGB(0xa0, 0xb1, 0xc2)));
+        assert_eq!(parse_color(" Red "), Some(ColorRGB(0xff, 0x00, 0x00)));
+        assert_eq!(parse_color("grey"), parse_color("gray"));
+        assert_eq!(parse_color("#ab"), None);
+        assert_eq!(parse_color("#abg"), None);
+        assert_eq!(parse_color("chartreuse"), None);
+    }
+
+    #[test]
+    fn entities() {
+        assert_eq!(
+            unescape("a &amp; b &lt;c&gt; &quot;&apos;"),
+            "a & b <c> \"'"
+        );
+        assert_eq!(unescape("&#65;&#x42;"), "AB");
+        assert_eq!(unescape("&nbsp; &#xzz; &"), "&nbsp; &#xzz; &");
+        assert_eq!(unescape("&&amp;"), "&&");
+    }
+
+    #[test]
+    fn nested_spans() {
+        let base = Style::default();
+        let segments = parse_pango(
+            "<span foreground=\"red\">a<b>b</b><span background='#00f' weight=\"bold\">c</span></span>d",
+            base,
+        );
+        assert_eq!(texts(&segments), vec!["a", "b", "c", "d"]);
+
+        let red = Some(ColorRGB(0xff, 0x00, 0x00));
+        assert_eq!(segments[0].style.foreground, red);
+        assert!(!segments[0].style.bold);
+        assert_eq!(segments[1].style.foreground, red);
+        assert!(segments[1].style.bold);
+        assert_eq!(segments[2].style.foreground, red);
+        assert_eq!(segments[2].style.background, Some(ColorRGB(0, 0, 0xff)));
+        assert!(segments[2].style.bold);
+        assert!(segments[3].style == Style::default());
+    }
+
+    #[test]
+    fn unknown_and_broken_markup() {
+        let base = Style {
+            foreground: Some(ColorRGB(1, 2, 3)),
+            ..Style::default()
+        };
+
+        // Closing more tags than opened keeps the style of the block
+        let segments = parse_pango("<tt>a</tt></span>b<br/>c", base.clone());
+        assert_eq!(texts(&segments), vec!["a", "b", "c"]);
+        assert!(segments.iter().all(|segment| segment.style == base));
+
+        let segments = parse_pango("1 < 2 &amp; 3", base);
+        assert_eq!(texts(&segments), vec!["1 < 2 & 3"]);
+
+        let segments = parse_pango(
+            "<span foreground=\"nope\" underline=\"single\">u</span>",
+            Style::default(),
+        );
+        assert_eq!(segments[0].style.foreground, None);
+        assert!(segments[0].style.underline);
+    }
+
+    #[test]
+    fn plain_text_blocks_are_not_parsed() {
+        let mut block = Block::new();
+        block.full_text("<b>a &amp; b</b>").color(ColorRGB(1, 2, 3));
+        let plain = segments(&block);
+        assert_eq!(texts(&plain), vec!["<b>a &amp; b</b>"]);
+        assert_eq!(plain[0].style.foreground, Some(ColorRGB(1, 2, 3)));
+
+        block.use_pango();
+        let pango = segments(&block);
+        assert_eq!(texts(&pango), vec!["a & b"]);
+        assert!(pango[0].style.bold);
+        assert_eq!(pango[0].style.foreground, Some(ColorRGB(1, 2, 3)));
+    }
+}
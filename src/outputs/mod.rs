@@ -0,0 +1,6 @@
+mod lemonbar;
+mod markup;
+mod tmux;
+
+pub use self::lemonbar::LemonbarOutput;
+pub use self::tmux::TmuxOutput;
@@ -0,0 +1,263 @@
+use crate::output::BarOutput;
+use crate::outputs::markup::{segments, Style};
+use crate::protocol::{Block, ColorRGB};
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Where the status line goes
+enum Target {
+    Stream(Box<dyn Write>),
+    /// The file is replaced by every new status line
+    File(PathBuf),
+}
+
+/// The output that draws the bar as a tmux status line
+///
+/// The colors of the blocks, as well as the Pango markup used by the builtin widgets, are
+/// translated to the `#[fg=#rrggbb]` style of tmux, and the urgent blocks are drawn reversed. The
+/// blocks are separated by ` | ` unless another separator is given.
+///
+/// The status line is either printed once, for a `#(...)` command in the tmux configuration, see
+/// `WidgetCollection::draw_once`:
+///
+/// ```no_run
+/// use i3monkit::outputs::TmuxOutput;
+/// use i3monkit::widgets::{BatteryWidget, DateTimeWidget};
+/// use i3monkit::WidgetCollection;
+///
+/// // set -g status-right "#(my-status-line)"
+/// let mut bar = WidgetCollection::new();
+/// bar.push(BatteryWidget::new(0));
+/// bar.push(DateTimeWidget::new());
+/// bar.draw_once(TmuxOutput::new(std::io::stdout()));
+/// ```
+///
+/// Or the bar is kept running and writes the status line to a file every time it changes, which
+/// tmux reads with `#(cat FILE)` every `status-interval` seconds. The widgets that need more than
+/// one update to show something useful, such as the CPU usage, only work this way.
+///
+/// ```no_run
+/// use i3monkit::outputs::TmuxOutput;
+/// use i3monkit::widgets::{CpuWidget, NetworkSpeedWidget};
+/// use i3monkit::WidgetCollection;
+///
+/// // set -g status-right "#(cat /tmp/status-line)"
+/// let mut bar = WidgetCollection::new();
+/// bar.push(NetworkSpeedWidget::new("eth0"));
+/// bar.push(CpuWidget::new(0));
+/// bar.update_loop(TmuxOutput::file("/tmp/status-line"));
+/// ```
+pub struct TmuxOutput {
+    target: Target,
+    separator: String,
+}
+
+impl TmuxOutput {
+    /// Create an output writing a status line per line
+    ///
+    /// **writer** Where the status line should be written, normally the stdout
+    pub fn new<T: Write + 'static>(writer: T) -> Self {
+        TmuxOutput {
+            target: Target::Stream(Box::new(writer)),
+            separator: " | ".to_string(),
+        }
+    }
+
+    /// Create an output replacing the file with every new status line
+    ///
+    /// **path** The path of the file tmux reads
+    pub fn file<P: AsRef<Path>>(path: P) -> Self {
+        TmuxOutput {
+            target: Target::File(path.as_ref().to_path_buf()),
+            separator: " | ".to_string(),
+        }
+    }
+
+    /// Set the text drawn between the blocks
+    pub fn separator(mut self, separator: &str) -> Self {
+        self.separator = separator.to_string();
+        self
+    }
+
+    /// Format a block, the style is reset at the end of the block
+    fn format_block(&self, block: &Block, out: &mut String) {
+        let urgent = block.is_urgent();
+        let mut current = Style::default();
+        let mut styled = false;
+        for segment in segments(block) {
+            if segment.style != current || (urgent && !styled) {
+                out.push_str(&format_style(&segment.style, urgent));
+                styled = true;
+            }
+            out.push_str(&segment.text.replace('#', "##"));
+            current = segment.style;
+        }
+
+        if urgent || current != Style::default() {
+            out.push_str("#[default]");
+        }
+    }
+}
+
+/// The tmux style of the text, which starts from the default style of the status line
+fn format_style(style: &Style, reverse: bool) -> String {
+    let color = |color: &ColorRGB| format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2);
+
+    let mut attrs = vec!["default".to_string()];
+    if let Some(ref foreground) = style.foreground {
+        attrs.push(format!("fg={}", color(foreground)));
+    }
+    if let Some(ref background) = style.background {
+        attrs.push(format!("bg={}", color(background)));
+    }
+    if style.bold {
+        attrs.push("bold".to_string());
+    }
+    if style.italic {
+        attrs.push("italics".to_string());
+    }
+    if style.underline {
+        attrs.push("underscore".to_string());
+    }
+    if reverse {
+        attrs.push("reverse".to_string());
+    }
+
+    format!("#[{}]", attrs.join(","))
+}
+
+impl BarOutput for TmuxOutput {
+    fn refresh(&mut self, blocks: &[Block]) {
+        let mut line = String::new();
+        for (idx, block) in blocks.iter().enumerate() {
+            if idx > 0 {
+                line.push_str(&self.separator.replace('#', "##"));
+            }
+            self.format_block(block, &mut line);
+        }
+
+        match self.target {
+            Target::Stream(ref mut writer) => {
+                writeln!(writer, "{}", line).expect("Cannot write");
+                writer.flush().ok();
+            }
+            Target::File(ref path) => {
+                // Replace the file at once, so that tmux never reads a partial line
+                let mut temp = path.clone().into_os_string();
+                temp.push(".tmp");
+                let result = std::fs::write(&temp, format!("{}\n", line))
+                    .and_then(|_| std::fs::rename(&temp, path));
+                if let Err(e) = result {
+                    eprintln!("i3monkit: Cannot write {}: {}", path.display(), e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// A writer whose output is still readable after it's given to the output
+    #[derive(Clone, Default)]
+    struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(data)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn draw(mut output: TmuxOutput, buffer: &Buffer, blocks: &[Block]) -> String {
+        output.refresh(blocks);
+        String::from_utf8(buffer.0.borrow().clone()).unwrap()
+    }
+
+    fn format(block: &Block) -> String {
+        let mut out = String::new();
+        TmuxOutput::new(std::io::sink()).format_block(block, &mut out);
+        out
+    }
+
+    #[test]
+    fn hashes_are_escaped() {
+        let mut block = Block::new();
+        block.full_text("#1 <b>#2</b>").use_pango();
+        assert_eq!(format(&block), "##1 #[default,bold]##2#[default]");
+
+        let mut plain = Block::new();
+        plain.full_text("#[fg=red]");
+        assert_eq!(format(&plain), "##[fg=red]");
+
+        let buffer = Buffer::default();
+        let output = TmuxOutput::new(buffer.clone()).separator(" # ");
+        assert_eq!(
+            draw(output, &buffer, &[plain.clone(), plain]),
+            "##[fg=red] ## ##[fg=red]\n"
+        );
+    }
+
+    #[test]
+    fn urgent_blocks_are_reversed() {
+        let mut urgent = Block::new();
+        urgent.full_text("hot").urgent(true);
+        assert_eq!(format(&urgent), "#[default,reverse]hot#[default]");
+
+        let mut styled = Block::new();
+        styled
+            .full_text("<span foreground=\"#f00\">hot</span> now")
+            .urgent(true)
+            .use_pango();
+        assert_eq!(
+            format(&styled),
+            "#[default,fg=#ff0000,reverse]hot#[default,reverse] now#[default]"
+        );
+    }
+
+    #[test]
+    fn the_style_is_reset_after_the_block() {
+        let mut plain = Block::new();
+        plain.full_text("50%");
+        assert_eq!(format(&plain), "50%");
+
+        let mut colored = Block::new();
+        colored.full_text("50%").color(ColorRGB(0x12, 0x34, 0x56));
+        assert_eq!(format(&colored), "#[default,fg=#123456]50%#[default]");
+
+        // Back to the default style within the block, nothing is left to reset
+        let mut markup = Block::new();
+        markup.full_text("<i>cpu</i> 50%").use_pango();
+        assert_eq!(format(&markup), "#[default,italics]cpu#[default] 50%");
+
+        let buffer = Buffer::default();
+        let output = TmuxOutput::new(buffer.clone());
+        assert_eq!(
+            draw(output, &buffer, &[colored, plain]),
+            "#[default,fg=#123456]50%#[default] | 50%\n"
+        );
+    }
+
+    #[test]
+    fn styles() {
+        let style = Style {
+            foreground: Some(ColorRGB(0xff, 0, 0)),
+            background: Some(ColorRGB(0, 0, 0x80)),
+            bold: true,
+            italic: true,
+            underline: true,
+        };
+        assert_eq!(
+            format_style(&style, true),
+            "#[default,fg=#ff0000,bg=#000080,bold,italics,underscore,reverse]"
+        );
+        assert_eq!(format_style(&Style::default(), false), "#[default]");
+    }
+}
@@ -1,21 +1,25 @@
 //! The abstraction for [i3 bar protcol](https://i3wm.org/docs/i3bar-protocol.html)
 
-use serde::{Serialize, Serializer};
-use std::io::{BufWriter, Write};
+use crate::error::Error;
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::io::{BufRead, BufWriter, ErrorKind, Write};
+use std::str::FromStr;
 
 /// The I3 protocol header
-#[derive(Serialize, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
 pub struct Header {
     /// Version number, currently must be 1
     version: u32,
     /// Sepecify the signal for stopping the program
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     stop_signal: Option<u32>,
     /// Specify the signal for resume the program
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     cont_signal: Option<u32>,
     /// If the mouse event is enabled
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     click_events: Option<bool>,
 }
 
@@ -29,23 +33,83 @@ impl Header {
         return ret;
     }
 
+    /// Set the signal i3bar sends to the status program when the bar is hidden
+    ///
+    /// By default i3bar sends `SIGSTOP`, which freezes every thread of the program. With a signal
+    /// that can be caught, such as `SIGUSR1`, the update loop pauses itself instead.
+    pub fn stop_signal(mut self, signal: u32) -> Self {
+        self.stop_signal = Some(signal);
+        self
+    }
+
+    /// Set the signal i3bar sends to the status program when the bar is shown again
+    ///
+    /// By default i3bar sends `SIGCONT`. The update loop refreshes every widget once it receives
+    /// this signal.
+    pub fn cont_signal(mut self, signal: u32) -> Self {
+        self.cont_signal = Some(signal);
+        self
+    }
+
+    /// Ask i3bar to report mouse clicks on the blocks
     pub fn click_events(mut self, enable: bool) -> Self {
         self.click_events = Some(enable);
         self
     }
+
+    /// The version number of the protocol
+    pub fn get_version(&self) -> u32 {
+        self.version
+    }
+
+    /// Check if the status program asks for the click events
+    pub fn click_events_enabled(&self) -> bool {
+        self.click_events.unwrap_or(false)
+    }
+
+    /// The signal sent to the status program when the bar is hidden, `SIGSTOP` by default
+    pub fn get_stop_signal(&self) -> u32 {
+        self.stop_signal.unwrap_or(libc::SIGSTOP as u32)
+    }
+
+    /// The signal sent to the status program when the bar is shown again, `SIGCONT` by default
+    pub fn get_cont_signal(&self) -> u32 {
+        self.cont_signal.unwrap_or(libc::SIGCONT as u32)
+    }
 }
 
 /// An RGB color
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ColorRGB(pub u8, pub u8, pub u8);
 
 impl Serialize for ColorRGB {
     fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
-        let color_string = format!("#{:.2x}{:02x}{:02x}", self.0, self.1, self.2);
+        let color_string = format!("#{:02x}{:02x}{:02x}", self.0, self.1, self.2);
         s.serialize_str(&color_string)
     }
 }
 
+impl<'de> Deserialize<'de> for ColorRGB {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(d)?;
+        text.parse().map_err(D::Error::custom)
+    }
+}
+
+impl FromStr for ColorRGB {
+    type Err = Error;
+
+    /// Parse the color in the `#rrggbb` format, the alpha channel of `#rrggbbaa` is ignored
+    fn from_str(text: &str) -> crate::Result<Self> {
+        let hex = text
+            .strip_prefix('#')
+            .filter(|hex| (hex.len() == 6 || hex.len() == 8) && hex.is_ascii())
+            .ok_or_else(|| Error::Parse(format!("Invalid color: {}", text)))?;
+        let channel = |pos: usize| u8::from_str_radix(&hex[pos..pos + 2], 16);
+        Ok(ColorRGB(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
 impl ColorRGB {
     pub fn red() -> Self {
         ColorRGB(255, 0, 0)
@@ -65,7 +129,7 @@ impl ColorRGB {
 }
 
 /// The option indicate what markup language should the i3bar use to parse the output
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum MarkupLang {
     /// Use plain-text
     Text,
@@ -82,8 +146,71 @@ impl Serialize for MarkupLang {
     }
 }
 
+impl<'de> Deserialize<'de> for MarkupLang {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        match String::deserialize(d)?.as_str() {
+            "pango" => Ok(MarkupLang::Pango),
+            _ => Ok(MarkupLang::Text),
+        }
+    }
+}
+
+/// The minimal width of a block
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum MinWidth {
+    /// The width in pixels
+    Pixels(u32),
+    /// The block is at least as wide as the given text
+    Text(String),
+}
+
+impl Serialize for MinWidth {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match self {
+            MinWidth::Pixels(px) => s.serialize_u32(*px),
+            MinWidth::Text(text) => s.serialize_str(text),
+        }
+    }
+}
+
+impl From<u32> for MinWidth {
+    fn from(px: u32) -> Self {
+        MinWidth::Pixels(px)
+    }
+}
+
+impl<'a> From<&'a str> for MinWidth {
+    fn from(text: &'a str) -> Self {
+        MinWidth::Text(text.to_string())
+    }
+}
+
+/// How the text is aligned when the block is wider than the text
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    /// Align the text to the left
+    Left,
+    /// Put the text in the center
+    Center,
+    /// Align the text to the right
+    Right,
+}
+
+impl Serialize for Align {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Align::Left => s.serialize_str("left"),
+            Align::Center => s.serialize_str("center"),
+            Align::Right => s.serialize_str("right"),
+        }
+    }
+}
+
 /// A block shown on the I3 status bar
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
 pub struct Block {
     name : String,
     instance : String,
@@ -95,10 +222,49 @@ pub struct Block {
     /// The text color
     #[serde(skip_serializing_if = "Option::is_none")]
     color: Option<ColorRGB>,
+    /// The background color
+    #[serde(skip_serializing_if = "Option::is_none")]
+    background: Option<ColorRGB>,
+    /// The border color
+    #[serde(skip_serializing_if = "Option::is_none")]
+    border: Option<ColorRGB>,
+    /// The width of the top border in pixels
+    #[serde(skip_serializing_if = "Option::is_none")]
+    border_top: Option<u32>,
+    /// The width of the right border in pixels
+    #[serde(skip_serializing_if = "Option::is_none")]
+    border_right: Option<u32>,
+    /// The width of the bottom border in pixels
+    #[serde(skip_serializing_if = "Option::is_none")]
+    border_bottom: Option<u32>,
+    /// The width of the left border in pixels
+    #[serde(skip_serializing_if = "Option::is_none")]
+    border_left: Option<u32>,
+    /// The minimal width of the block
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_width: Option<MinWidth>,
+    /// The text alignment within the block
+    #[serde(skip_serializing_if = "Option::is_none")]
+    align: Option<Align>,
+    /// If the block should be highlighted as urgent
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    urgent: bool,
+    /// If a separator line should be drawn after the block
+    #[serde(skip_serializing_if = "Option::is_none")]
+    separator: Option<bool>,
+    /// The gap after the block in pixels
+    #[serde(skip_serializing_if = "Option::is_none")]
+    separator_block_width: Option<u32>,
     /// The markup language options
     markup: MarkupLang,
 }
 
+impl Default for Block {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Block {
     /// Create a new block
     pub fn new() -> Self {
@@ -108,6 +274,17 @@ impl Block {
             full_text: "".to_string(),
             short_text: "".to_string(),
             color: None,
+            background: None,
+            border: None,
+            border_top: None,
+            border_right: None,
+            border_bottom: None,
+            border_left: None,
+            min_width: None,
+            align: None,
+            urgent: false,
+            separator: None,
+            separator_block_width: None,
             markup: MarkupLang::Text,
         }
     }
@@ -153,6 +330,74 @@ impl Block {
         self
     }
 
+    /// Set the background color
+    pub fn background(&mut self, color: ColorRGB) -> &mut Self {
+        self.background = Some(color);
+        self
+    }
+
+    /// Set the border color
+    pub fn border(&mut self, color: ColorRGB) -> &mut Self {
+        self.border = Some(color);
+        self
+    }
+
+    /// Set the width of the top border in pixels
+    pub fn border_top(&mut self, px: u32) -> &mut Self {
+        self.border_top = Some(px);
+        self
+    }
+
+    /// Set the width of the right border in pixels
+    pub fn border_right(&mut self, px: u32) -> &mut Self {
+        self.border_right = Some(px);
+        self
+    }
+
+    /// Set the width of the bottom border in pixels
+    pub fn border_bottom(&mut self, px: u32) -> &mut Self {
+        self.border_bottom = Some(px);
+        self
+    }
+
+    /// Set the width of the left border in pixels
+    pub fn border_left(&mut self, px: u32) -> &mut Self {
+        self.border_left = Some(px);
+        self
+    }
+
+    /// Set the minimal width of the block, either in pixels or as wide as a text
+    ///
+    /// A fixed width keeps the blocks from jittering when the length of the text changes.
+    pub fn min_width<W: Into<MinWidth>>(&mut self, width: W) -> &mut Self {
+        self.min_width = Some(width.into());
+        self
+    }
+
+    /// Set how the text is aligned when the block is wider than the text
+    pub fn align(&mut self, align: Align) -> &mut Self {
+        self.align = Some(align);
+        self
+    }
+
+    /// Mark the block as urgent, so that i3bar highlights it
+    pub fn urgent(&mut self, urgent: bool) -> &mut Self {
+        self.urgent = urgent;
+        self
+    }
+
+    /// Set if a separator line should be drawn after the block
+    pub fn separator(&mut self, separator: bool) -> &mut Self {
+        self.separator = Some(separator);
+        self
+    }
+
+    /// Set the gap after the block in pixels
+    pub fn separator_block_width(&mut self, px: u32) -> &mut Self {
+        self.separator_block_width = Some(px);
+        self
+    }
+
     /// Make the block uses the pango markup language
     pub fn use_pango(&mut self) -> &mut Self {
         self.markup = MarkupLang::Pango;
@@ -164,20 +409,282 @@ impl Block {
         self.markup = MarkupLang::Text;
         self
     }
+
+    /// The name of the block
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// The instance of the block
+    pub fn get_instance(&self) -> &str {
+        &self.instance
+    }
+
+    /// The full text shown on the bar
+    pub fn get_full_text(&self) -> &str {
+        &self.full_text
+    }
+
+    /// The short alternative of the text, which is empty if the block has none
+    pub fn get_short_text(&self) -> &str {
+        &self.short_text
+    }
+
+    /// The color of the text
+    pub fn get_color(&self) -> Option<&ColorRGB> {
+        self.color.as_ref()
+    }
+
+    /// The color of the background
+    pub fn get_background(&self) -> Option<&ColorRGB> {
+        self.background.as_ref()
+    }
+
+    /// The color of the border
+    pub fn get_border(&self) -> Option<&ColorRGB> {
+        self.border.as_ref()
+    }
+
+    /// Check if the block is highlighted as urgent
+    pub fn is_urgent(&self) -> bool {
+        self.urgent
+    }
+
+    /// The markup language of the text
+    pub fn get_markup(&self) -> &MarkupLang {
+        &self.markup
+    }
+
+    /// Give the block a name if it doesn't have one, so that the clicks can be routed back
+    pub(crate) fn default_name(&mut self, name: &str) {
+        if self.name.is_empty() {
+            self.name = name.to_string();
+        }
+    }
+
+    /// Create a click event as if the user clicked this block
+    pub(crate) fn click_event(&self, button: u32) -> ClickEvent {
+        ClickEvent {
+            name: self.name.clone(),
+            instance: self.instance.clone(),
+            button,
+            ..Default::default()
+        }
+    }
+
+    /// Check if the click event is produced by clicking this block
+    pub(crate) fn is_clicked(&self, event: &ClickEvent) -> bool {
+        self.name == event.name && self.instance == event.instance
+    }
+}
+
+/// A mouse click event sent by i3bar
+///
+/// i3bar only reports clicks when the click events are enabled in the protocol header. The `name`
+/// and `instance` fields are copied from the block that has been clicked.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ClickEvent {
+    /// The name of the clicked block
+    pub name: String,
+    /// The instance of the clicked block
+    pub instance: String,
+    /// The mouse button, 1 for left, 2 for middle, 3 for right, 4 and 5 for scrolling
+    pub button: u32,
+    /// The modifiers held when clicking, for example, "Shift" or "Mod4"
+    pub modifiers: Vec<String>,
+    /// The X11 root window coordinate of the click
+    pub x: i32,
+    /// The X11 root window coordinate of the click
+    pub y: i32,
+    /// The coordinate of the click relative to the top left corner of the block
+    pub relative_x: i32,
+    /// The coordinate of the click relative to the top left corner of the block
+    pub relative_y: i32,
+    /// The coordinate of the click relative to the output
+    pub output_x: i32,
+    /// The coordinate of the click relative to the output
+    pub output_y: i32,
+    /// The width of the clicked block in pixels
+    pub width: i32,
+    /// The height of the clicked block in pixels
+    pub height: i32,
+}
+
+/// How many reads in a row may fail before the click event reader gives up on the input
+const MAX_READ_ERRORS: u32 = 16;
+
+/// The reader for the infinite click event array i3bar writes to the status program
+///
+/// i3bar writes a `[` first and then one event per line, each of them separated by a comma.
+/// Lines that can not be parsed are skipped, and the reader only stops at the end of the input.
+pub struct ClickEventReader<R: BufRead>(R);
+
+impl<R: BufRead> ClickEventReader<R> {
+    /// Create a new click event reader
+    ///
+    /// **input** Where the click events should be read from, normally the stdin
+    pub fn new(input: R) -> Self {
+        ClickEventReader(input)
+    }
+}
+
+impl<R: BufRead> Iterator for ClickEventReader<R> {
+    type Item = ClickEvent;
+    fn next(&mut self) -> Option<ClickEvent> {
+        let mut line = Vec::new();
+        let mut errors = 0;
+        loop {
+            line.clear();
+            match self.0.read_until(b'\n', &mut line) {
+                Ok(0) => return None,
+                Ok(_) => errors = 0,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                // Give up on an input that keeps failing rather than spinning on it
+                Err(_) if errors >= MAX_READ_ERRORS => return None,
+                Err(_) => {
+                    errors += 1;
+                    continue;
+                }
+            }
+
+            let line = match std::str::from_utf8(&line) {
+                Ok(line) => line,
+                Err(_) => continue,
+            };
+
+            let payload = line
+                .trim()
+                .trim_start_matches(['[', ','])
+                .trim_end_matches(',')
+                .trim();
+
+            if payload.is_empty() {
+                continue;
+            }
+
+            if let Ok(event) = serde_json::from_str(payload) {
+                return Some(event);
+            }
+        }
+    }
+}
+
+/// The reader for the output of a status program, such as i3status or i3monkit itself
+///
+/// The status program writes the protocol header first, and then an infinite array, whose
+/// elements are the arrays of blocks that should be shown on the bar, one array per line. The
+/// header is read when the reader is created, and the reader iterates over the block arrays
+/// afterwards. Lines that can not be parsed are skipped.
+///
+/// ```no_run
+/// use i3monkit::I3ProtocolReader;
+/// use std::process::{Command, Stdio};
+///
+/// let i3status = Command::new("i3status").stdout(Stdio::piped()).spawn().unwrap();
+/// let output = std::io::BufReader::new(i3status.stdout.unwrap());
+/// let reader = I3ProtocolReader::new(output).unwrap();
+///
+/// println!("Click events: {}", reader.header().click_events_enabled());
+/// for blocks in reader {
+///     println!("{:?}", blocks);
+/// }
+/// ```
+pub struct I3ProtocolReader<R: BufRead> {
+    input: R,
+    header: Header,
+}
+
+impl<R: BufRead> I3ProtocolReader<R> {
+    /// Create a new reader and read the protocol header
+    ///
+    /// **input** Where the status program output should be read from
+    pub fn new(mut input: R) -> crate::Result<Self> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                return Err(Error::Parse("No protocol header".to_string()));
+            }
+
+            if !line.trim().is_empty() {
+                break;
+            }
+        }
+
+        let header = serde_json::from_str(line.trim())
+            .map_err(|e| Error::Parse(format!("Invalid protocol header: {}", e)))?;
+
+        Ok(I3ProtocolReader { input, header })
+    }
+
+    /// The protocol header written by the status program
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+}
+
+impl<R: BufRead> Iterator for I3ProtocolReader<R> {
+    type Item = Vec<Block>;
+    fn next(&mut self) -> Option<Vec<Block>> {
+        let mut line = Vec::new();
+        let mut errors = 0;
+        loop {
+            line.clear();
+            match self.input.read_until(b'\n', &mut line) {
+                Ok(0) => return None,
+                Ok(_) => errors = 0,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                // Give up on an input that keeps failing rather than spinning on it
+                Err(_) if errors >= MAX_READ_ERRORS => return None,
+                Err(_) => {
+                    errors += 1;
+                    continue;
+                }
+            }
+
+            let line = match std::str::from_utf8(&line) {
+                Ok(line) => line,
+                Err(_) => continue,
+            };
+
+            if let Some(blocks) = parse_blocks(line) {
+                return Some(blocks);
+            }
+        }
+    }
+}
+
+/// Parse a line of the infinite block array a status program writes to i3bar
+///
+/// The line is an array of blocks, which may be preceded or followed by the comma separating it
+/// from the other arrays, or preceded by the `[` opening the infinite array. None is returned if the line doesn't
+/// contain any array.
+pub(crate) fn parse_blocks(line: &str) -> Option<Vec<Block>> {
+    let payload = line.trim().trim_matches(',').trim();
+    if let Ok(blocks) = serde_json::from_str(payload) {
+        return Some(blocks);
+    }
+
+    let payload = payload.strip_prefix('[')?;
+    serde_json::from_str(payload).ok()
 }
 
 /// The abstraction for a i3 protocol instance
-pub struct I3Protocol<T: Write>(BufWriter<T>);
+pub struct I3Protocol<T: Write> {
+    writer: BufWriter<T>,
+    header: Header,
+}
 
 impl<T: Write> I3Protocol<T> {
     fn write<S: AsRef<str>>(&mut self, data: S) {
-        self.0
+        self.writer
             .write_all(AsRef::<str>::as_ref(&data).as_bytes())
             .expect("Cannot write");
-        self.0.write_all(b"\n").expect("Cannot write");
-        self.0.flush().ok();
+        self.writer.write_all(b"\n").expect("Cannot write");
+        self.writer.flush().ok();
     }
-    fn write_json<S: Serialize>(&mut self, data: &S) {
+    fn write_json<S: Serialize + ?Sized>(&mut self, data: &S) {
         if let Ok(serialized) = serde_json::to_string(data) {
             self.write(serialized);
         }
@@ -187,16 +694,35 @@ impl<T: Write> I3Protocol<T> {
     ///
     /// **wr** Where the protocol message should be dumped
     pub fn new(header: Header, wr: T) -> Self {
-        let mut ret = I3Protocol(BufWriter::new(wr));
-        ret.write_json(&header);
+        let mut ret = I3Protocol {
+            writer: BufWriter::new(wr),
+            header,
+        };
+        let header = serde_json::to_string(&ret.header).expect("Cannot serialize the header");
+        ret.write(header);
         ret.write("[ []");
         ret
     }
 
+    /// Check if i3bar has been asked to report the click events
+    pub fn click_events_enabled(&self) -> bool {
+        self.header.click_events_enabled()
+    }
+
+    /// The signal i3bar sends when the bar is hidden
+    pub fn stop_signal(&self) -> u32 {
+        self.header.get_stop_signal()
+    }
+
+    /// The signal i3bar sends when the bar is shown again
+    pub fn cont_signal(&self) -> u32 {
+        self.header.get_cont_signal()
+    }
+
     /// Refresh the bar
     ///
     /// **status** The new list of blocks `i3bar` should redraw
-    pub fn refresh(&mut self, status: &Vec<Block>) {
+    pub fn refresh(&mut self, status: &[Block]) {
         self.write(",");
         self.write_json(status)
     }
@@ -207,3 +733,155 @@ impl<T: Write> Drop for I3Protocol<T> {
         self.write("]");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clicks(input: &[u8]) -> Vec<ClickEvent> {
+        ClickEventReader::new(input).collect()
+    }
+
+    #[test]
+    fn click_events_follow_the_array_framing() {
+        let input = b"[\n{\"name\":\"cpu\",\"button\":1}\n,{\"name\":\"battery\",\"instance\":\"0\",\"button\":3}\n";
+        let events = clicks(input);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].name, "cpu");
+        assert_eq!(events[0].button, 1);
+        assert_eq!(events[1].name, "battery");
+        assert_eq!(events[1].instance, "0");
+        assert_eq!(events[1].button, 3);
+    }
+
+    #[test]
+    fn click_event_on_the_opening_line() {
+        let events = clicks(b"[{\"name\":\"cpu\",\"button\":2},\n");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].button, 2);
+    }
+
+    #[test]
+    fn bad_click_lines_are_skipped() {
+        let input = b"[\n,{\"name\":\xff\xfe}\n,not json\n\n,{\"name\":\"cpu\",\"button\":1}\n";
+        let events = clicks(input);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].name, "cpu");
+    }
+
+    fn read_bar(output: &str) -> (Header, Vec<Vec<Block>>) {
+        let reader = I3ProtocolReader::new(output.as_bytes()).unwrap();
+        let header = reader.header().clone();
+        (header, reader.collect())
+    }
+
+    #[test]
+    fn header_with_click_events() {
+        let (header, _) = read_bar("{\"version\":1,\"click_events\":true,\"stop_signal\":10}\n[\n");
+        assert_eq!(header.get_version(), 1);
+        assert!(header.click_events_enabled());
+        assert_eq!(header.get_stop_signal(), 10);
+        assert_eq!(header.get_cont_signal(), libc::SIGCONT as u32);
+    }
+
+    #[test]
+    fn header_without_click_events() {
+        let (header, _) = read_bar("\n{\"version\":1}\n[\n");
+        assert!(!header.click_events_enabled());
+        assert_eq!(header.get_stop_signal(), libc::SIGSTOP as u32);
+    }
+
+    #[test]
+    fn missing_or_invalid_header() {
+        assert!(I3ProtocolReader::new(&b""[..]).is_err());
+        assert!(I3ProtocolReader::new(&b"[{\"full_text\":\"a\"}]\n"[..]).is_err());
+    }
+
+    #[test]
+    fn block_arrays_after_the_opening_bracket() {
+        let output = concat!(
+            "{\"version\":1}\n",
+            "[[{\"full_text\":\"first\"}]\n",
+            ",[{\"full_text\":\"second\",\"urgent\":true}]\n",
+            "[{\"full_text\":\"third\"}],\n",
+            "]\n",
+        );
+        let (_, arrays) = read_bar(output);
+        let texts: Vec<Vec<_>> = arrays
+            .iter()
+            .map(|blocks| blocks.iter().map(Block::get_full_text).collect())
+            .collect();
+        assert_eq!(texts, vec![vec!["first"], vec!["second"], vec!["third"]]);
+        assert!(arrays[1][0].is_urgent());
+    }
+
+    #[test]
+    fn malformed_lines_are_skipped() {
+        let output =
+            "{\"version\":1}\n[\n,[{\"full_text\":\n,not json\n,[{\"full_text\":\"ok\"}]\n";
+        let (_, arrays) = read_bar(output);
+        assert_eq!(arrays.len(), 1);
+        assert_eq!(arrays[0][0].get_full_text(), "ok");
+        assert!(parse_blocks("[{\"full_text\":").is_none());
+        assert!(parse_blocks(",").is_none());
+    }
+
+    #[test]
+    fn lines_that_are_not_utf8_are_skipped() {
+        let output = b"{\"version\":1}\n[\n\
+                       [{\"full_text\":\"\xff\"}]\n\
+                       ,[{\"full_text\":\"ok\"}]\n";
+        let reader = I3ProtocolReader::new(&output[..]).unwrap();
+        let arrays: Vec<_> = reader.collect();
+        assert_eq!(arrays.len(), 1);
+        assert_eq!(arrays[0][0].get_full_text(), "ok");
+    }
+
+    #[test]
+    fn reads_the_output_of_the_bar() {
+        let mut block = Block::new();
+        block
+            .name("cpu")
+            .instance("0")
+            .full_text("<b>50%</b>")
+            .short_text("50")
+            .color(ColorRGB(0x12, 0x34, 0x56))
+            .min_width("100%")
+            .align(Align::Right)
+            .separator(false)
+            .use_pango();
+        let mut other = Block::new();
+        other.full_text("idle").min_width(30).border_top(2);
+
+        let mut output = Vec::new();
+        {
+            let header = Header::new(1).click_events(true);
+            let mut bar = I3Protocol::new(header, &mut output);
+            bar.refresh(&[block.clone(), other.clone()]);
+            bar.refresh(&[other.clone()]);
+        }
+
+        let (header, arrays) = read_bar(std::str::from_utf8(&output).unwrap());
+        assert!(header.click_events_enabled());
+        assert_eq!(
+            arrays,
+            vec![vec![], vec![block, other.clone()], vec![other]]
+        );
+    }
+
+    #[test]
+    fn color_round_trip() {
+        let color: ColorRGB = serde_json::from_str("\"#0a0b0c\"").unwrap();
+        assert_eq!(color, ColorRGB(0x0a, 0x0b, 0x0c));
+        assert_eq!(serde_json::to_string(&color).unwrap(), "\"#0a0b0c\"");
+
+        // The alpha channel is dropped
+        let color: ColorRGB = serde_json::from_str("\"#ff00807f\"").unwrap();
+        assert_eq!(color, ColorRGB(0xff, 0x00, 0x80));
+        assert_eq!(serde_json::to_string(&color).unwrap(), "\"#ff0080\"");
+
+        assert!("#fff".parse::<ColorRGB>().is_err());
+        assert!("0a0b0c".parse::<ColorRGB>().is_err());
+        assert!("#0a0b0g".parse::<ColorRGB>().is_err());
+    }
+}
@@ -0,0 +1,278 @@
+//! The registry that creates widgets by type name
+
+use crate::error::{Error, Result};
+use crate::widget::Widget;
+use crate::widgets::*;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// A widget created from the options in the configuration
+pub(crate) enum Created {
+    /// The widget is updated on the main thread
+    Local(Box<dyn Widget>),
+    /// The widget is updated on its own thread, with the default timeout of each update
+    Threaded(Box<dyn Widget + Send>, Duration),
+}
+
+/// Creates a widget from the options in the configuration
+type Factory = Box<dyn Fn(toml::Value) -> Result<Created>>;
+
+/// How long a command widget may run before it's marked stale, unless `timeout` is set
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The registry that maps a widget type name to the function creating the widget
+///
+/// This is how the widget sections of a configuration file become widgets, see the `config`
+/// module. Every builtin widget is registered by default, and a downstream crate can register its
+/// own widgets, which are then usable from the same configuration format.
+///
+/// The function creating a widget takes the options of the widget, which can be any type that
+/// serde deserializes. The common options, such as `name`, `color` and `interval`, are handled by
+/// the configuration and never passed to the function.
+///
+/// ```no_run
+/// use i3monkit::config::{default_config_path, Config};
+/// use i3monkit::{Block, Header, I3Protocol, Widget, WidgetRegistry, WidgetUpdate};
+/// use serde::Deserialize;
+///
+/// struct Greeter(String);
+///
+/// impl Widget for Greeter {
+///     fn update(&mut self) -> Option<WidgetUpdate> {
+///         Some(WidgetUpdate {
+///             refresh_interval: std::time::Duration::new(3600, 0),
+///             aligned: false,
+///             data: Some(Block::new().append_full_text(&self.0).clone().into()),
+///         })
+///     }
+/// }
+///
+/// // The options of `[[widget]] type = "greeter"` sections
+/// #[derive(Deserialize)]
+/// struct GreeterOptions {
+///     text: String,
+/// }
+///
+/// let mut registry = WidgetRegistry::new();
+/// registry.register("greeter", |options: GreeterOptions| Ok(Greeter(options.text)));
+///
+/// let config = Config::load(default_config_path()).unwrap();
+/// let mut bar = config.build_with(&registry).unwrap();
+/// bar.update_loop(I3Protocol::new(config.header(), std::io::stdout()));
+/// ```
+pub struct WidgetRegistry {
+    factories: HashMap<String, Factory>,
+}
+
+impl Default for WidgetRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CpuOptions {
+    core: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NetworkOptions {
+    interface: String,
+    #[serde(default)]
+    hide_when_down: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BatteryOptions {
+    #[serde(default)]
+    index: u32,
+    #[serde(default)]
+    hide_when_full: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct VolumeOptions {
+    device: String,
+    mixer: String,
+    index: u32,
+}
+
+impl Default for VolumeOptions {
+    fn default() -> Self {
+        VolumeOptions {
+            device: "default".to_string(),
+            mixer: "Master".to_string(),
+            index: 0,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DateTimeOptions {}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StockOptions {
+    api_key: String,
+    symbols: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CommandOptions {
+    command: String,
+    #[serde(default)]
+    instance: String,
+    #[serde(default)]
+    pango: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StreamOptions {
+    command: String,
+    #[serde(default)]
+    pango: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct I3StatusOptions {
+    #[serde(default = "default_i3status_command")]
+    command: String,
+}
+
+fn default_i3status_command() -> String {
+    "i3status".to_string()
+}
+
+impl WidgetRegistry {
+    /// Creates a registry with all the builtin widgets
+    pub fn new() -> Self {
+        let mut registry = Self::empty();
+
+        registry
+            .register("cpu", |options: CpuOptions| {
+                CpuWidget::try_new(options.core)
+            })
+            .register("network", |options: NetworkOptions| {
+                Ok(NetworkSpeedWidget::try_new(&options.interface)?
+                    .hide_when_down(options.hide_when_down))
+            })
+            .register("battery", |options: BatteryOptions| {
+                Ok(BatteryWidget::new(options.index).hide_when_full(options.hide_when_full))
+            })
+            .register("volume", |options: VolumeOptions| {
+                Ok(VolumeWidget::new(
+                    &options.device,
+                    &options.mixer,
+                    options.index,
+                ))
+            })
+            .register("datetime", |_: DateTimeOptions| Ok(DateTimeWidget::new()))
+            .register_threaded("command", COMMAND_TIMEOUT, |options: CommandOptions| {
+                let widget = CommandWidget::new(&options.command).instance(&options.instance);
+                Ok(if options.pango {
+                    widget.use_pango()
+                } else {
+                    widget
+                })
+            })
+            .register("stream", |options: StreamOptions| {
+                let widget = StreamWidget::new(&options.command);
+                Ok(if options.pango {
+                    widget.use_pango()
+                } else {
+                    widget
+                })
+            })
+            .register("i3status", |options: I3StatusOptions| {
+                Ok(I3StatusWidget::new(&options.command))
+            })
+            .register("stock", |options: StockOptions| {
+                if options.symbols.is_empty() {
+                    return Err(Error::Parse("No stock symbol".to_string()));
+                }
+                Ok(StockTicker::new(&options.api_key, &options.symbols))
+            });
+
+        registry
+    }
+
+    /// Creates a registry without any widget
+    pub fn empty() -> Self {
+        WidgetRegistry {
+            factories: HashMap::new(),
+        }
+    }
+
+    /// Register a widget type, replacing the type registered with the same name
+    ///
+    /// **name** The type name used in the configuration
+    /// **factory** The function that creates the widget from its options
+    pub fn register<O, W, F>(&mut self, name: &str, factory: F) -> &mut Self
+    where
+        O: DeserializeOwned,
+        W: Widget + 'static,
+        F: Fn(O) -> Result<W> + 'static,
+    {
+        let factory = move |options: toml::Value| -> Result<Created> {
+            Ok(Created::Local(Box::new(factory(options.try_into()?)?)))
+        };
+        self.factories.insert(name.to_string(), Box::new(factory));
+        self
+    }
+
+    /// Register a widget type whose widgets are updated on their own thread, replacing the type
+    /// registered with the same name
+    ///
+    /// This is meant for the widgets whose update may be slow, which would block every other
+    /// widget otherwise, see `WidgetCollection::push_threaded`. The `timeout` option of the
+    /// configuration overrides the default timeout.
+    ///
+    /// **name** The type name used in the configuration
+    /// **timeout** How long an update may take before the widget is considered stale
+    /// **factory** The function that creates the widget from its options
+    pub fn register_threaded<O, W, F>(
+        &mut self,
+        name: &str,
+        timeout: Duration,
+        factory: F,
+    ) -> &mut Self
+    where
+        O: DeserializeOwned,
+        W: Widget + Send + 'static,
+        F: Fn(O) -> Result<W> + 'static,
+    {
+        let factory = move |options: toml::Value| -> Result<Created> {
+            Ok(Created::Threaded(
+                Box::new(factory(options.try_into()?)?),
+                timeout,
+            ))
+        };
+        self.factories.insert(name.to_string(), Box::new(factory));
+        self
+    }
+
+    /// Check if the widget type is registered
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories.contains_key(name)
+    }
+
+    /// Create a widget of the type from its options
+    pub(crate) fn create(&self, name: &str, options: toml::Value) -> Result<Created> {
+        match self.factories.get(name) {
+            Some(factory) => factory(options),
+            None => Err(Error::Parse("Unknown widget type".to_string())),
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Forward the UNIX signals to the update loop
+
+use crate::widget::Message;
+
+use libc::c_int;
+
+use std::fs::File;
+use std::io::{ErrorKind, Read, Result};
+use std::os::unix::io::FromRawFd;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::mpsc::Sender;
+
+/// The write end of the pipe the signal handler reports to
+static SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+extern "C" fn on_signal(signo: c_int) {
+    let fd = SIGNAL_PIPE.load(Ordering::Relaxed);
+    if fd < 0 {
+        return;
+    }
+
+    // Only async-signal-safe functions can be used here, so just write the signal number to the
+    // pipe and let the forwarding thread do the rest. The pipe doesn't block, if it's full the
+    // write fails with EAGAIN and the signal is dropped, as the loop has plenty to catch up with.
+    unsafe {
+        let errno = *libc::__errno_location();
+        let byte = signo as u8;
+        libc::write(fd, &byte as *const u8 as *const libc::c_void, 1);
+        *libc::__errno_location() = errno;
+    }
+}
+
+/// Create the pipe the signal handler writes to, and the thread that forwards the signals from the
+/// pipe to the update loop
+fn start_forwarding(message_tx: Sender<Message>) -> Result<()> {
+    let mut fds = [0; 2];
+
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    // The signal handler must never block, while the forwarding thread waits for the signals
+    if unsafe { libc::fcntl(fds[1], libc::F_SETFL, libc::O_NONBLOCK) } != 0 {
+        let error = std::io::Error::last_os_error();
+        unsafe {
+            libc::close(fds[0]);
+            libc::close(fds[1]);
+        }
+        return Err(error);
+    }
+
+    SIGNAL_PIPE.store(fds[1], Ordering::SeqCst);
+
+    let mut reader = unsafe { File::from_raw_fd(fds[0]) };
+
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 64];
+        loop {
+            let size = match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(size) => size,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            };
+
+            for signo in buf[..size].iter() {
+                if message_tx.send(Message::Signal(*signo as c_int)).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Install the handlers for the given signals and deliver them to the update loop as messages
+///
+/// This can be called again to handle more signals, the signals are delivered to the sender given
+/// the first time.
+///
+/// **signals** The signals to handle
+/// **message_tx** The sender of the update loop
+pub(crate) fn forward_signals(signals: &[c_int], message_tx: Sender<Message>) -> Result<()> {
+    if SIGNAL_PIPE.load(Ordering::SeqCst) < 0 {
+        start_forwarding(message_tx)?;
+    }
+
+    for signo in signals {
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_signal as extern "C" fn(c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+
+            if libc::sigaction(*signo, &action, std::ptr::null_mut()) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,79 @@
+//! Watch a file for changes with inotify
+
+use std::ffi::CString;
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read, Result};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::FromRawFd;
+use std::path::Path;
+use std::time::Duration;
+
+/// How long to wait for the writes following the first change to settle down
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// Call the function every time the file is written, or replaced by another file
+///
+/// The directory of the file is watched instead of the file itself, so that the file is still
+/// watched after an editor replaces it. The watching stops once the function returns false.
+pub(crate) fn watch_file<F: FnMut() -> bool + Send + 'static>(
+    path: &Path,
+    mut on_change: F,
+) -> Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Not a file"))?
+        .to_owned();
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let dir = CString::new(dir.as_os_str().as_bytes())?;
+
+    let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+    if fd < 0 {
+        return Err(Error::last_os_error());
+    }
+
+    let mut inotify = unsafe { File::from_raw_fd(fd) };
+
+    let mask = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_CREATE;
+    if unsafe { libc::inotify_add_watch(fd, dir.as_ptr(), mask) } < 0 {
+        return Err(Error::last_os_error());
+    }
+
+    std::thread::spawn(move || {
+        let header_size = std::mem::size_of::<libc::inotify_event>();
+        let mut buf = [0u8; 4096];
+        loop {
+            let size = match inotify.read(&mut buf) {
+                Ok(0) => break,
+                Ok(size) => size,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            };
+
+            let mut changed = false;
+            let mut offset = 0;
+            while offset + header_size <= size {
+                let event = unsafe {
+                    std::ptr::read_unaligned(buf[offset..].as_ptr() as *const libc::inotify_event)
+                };
+                let name_start = offset + header_size;
+                offset = (name_start + event.len as usize).min(size);
+
+                // The name is padded with NUL bytes
+                let event_name = buf[name_start..offset].split(|b| *b == 0).next();
+                changed |= event_name == Some(name.as_bytes());
+            }
+
+            if changed {
+                std::thread::sleep(SETTLE_TIME);
+                if !on_change() {
+                    break;
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
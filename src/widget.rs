@@ -1,11 +1,32 @@
 //! The widget infrastructure
 
-use crate::protocol::{Block, I3Protocol};
+use crate::clock::suspended_time;
+use crate::control::{Command, ControlHandle};
+use crate::error::{Error, Result};
+use crate::ipc;
+use crate::output::{BarOutput, ClickSender};
+use crate::protocol::{Block, ClickEvent, ColorRGB};
+use crate::signal::forward_signals;
+use crate::worker::Worker;
 use std::cmp::Ordering;
 use std::collections::BinaryHeap;
-use std::io::Write;
-use std::thread::sleep;
-use std::time::{Duration, SystemTime};
+use std::convert::TryFrom;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The delay before the first retry of a failed widget
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// The longest delay between the retries of a failed widget
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+/// The longest time the update loop sleeps, so that a system resume is noticed soon
+const MAX_SLEEP: Duration = Duration::from_secs(2);
+/// The shortest suspension that makes the update loop refresh every widget
+const MIN_SUSPENSION: Duration = Duration::from_secs(1);
+/// The default window in which the due widgets are updated together
+const DEFAULT_BATCH_WINDOW: Duration = Duration::from_millis(50);
 
 /// An update of a widget.
 ///
@@ -15,11 +36,66 @@ use std::time::{Duration, SystemTime};
 /// For some use cases, it's possible that we do not deliver any update, but
 /// requires the widget framework to call the widget again after some time.
 /// This can be done by passing the widget update with an empty data payload.
+#[derive(Default)]
 pub struct WidgetUpdate {
     /// Amount of time until the widget gets refresh
     pub refresh_interval: Duration,
-    /// Data payload to update, None indicates
-    pub data: Option<Block>,
+    /// Align the refresh to the wall clock.
+    ///
+    /// If this is set, the widget is refreshed at the next multiple of the refresh interval since
+    /// the UNIX epoch. For example, with an interval of 1 second the widget is refreshed at the
+    /// start of every second, and with an interval of 60 seconds at the start of every minute.
+    pub aligned: bool,
+    /// Data payload to update, None indicates the blocks on the bar are kept
+    pub data: Option<WidgetData>,
+}
+
+/// The data payload of a widget update
+///
+/// A widget either draws a single block, or a list of blocks whose length may change from one
+/// update to another, for example, one block per CPU core.
+///
+/// A widget can also hide itself for now, for example, the battery widget when the battery is
+/// full. Unlike returning None from `Widget::update`, a hidden widget is still updated and shows
+/// up again once it delivers any block.
+pub enum WidgetData {
+    /// The widget shows a single block
+    Single(Block),
+    /// The widget shows a list of blocks
+    Multiple(Vec<Block>),
+    /// The widget doesn't show anything until the next update
+    Hidden,
+}
+
+impl WidgetData {
+    /// Get all the blocks in the payload
+    pub fn blocks_mut(&mut self) -> &mut [Block] {
+        match self {
+            WidgetData::Single(block) => std::slice::from_mut(block),
+            WidgetData::Multiple(blocks) => blocks,
+            WidgetData::Hidden => &mut [],
+        }
+    }
+
+    fn into_blocks(self) -> Vec<Block> {
+        match self {
+            WidgetData::Single(block) => vec![block],
+            WidgetData::Multiple(blocks) => blocks,
+            WidgetData::Hidden => vec![],
+        }
+    }
+}
+
+impl From<Block> for WidgetData {
+    fn from(block: Block) -> Self {
+        WidgetData::Single(block)
+    }
+}
+
+impl From<Vec<Block>> for WidgetData {
+    fn from(blocks: Vec<Block>) -> Self {
+        WidgetData::Multiple(blocks)
+    }
 }
 
 /// Widget decorator, which modifies the block returned by the widget
@@ -41,15 +117,72 @@ pub struct WidgetDecorator<T: Widget, F: FnMut(&mut Block)> {
 
 /// The trait for an widget.
 ///
-/// A widget maintains a dynamic block on the i3bar
+/// A widget maintains one or more dynamic blocks on the i3bar
+///
+/// A widget implements either `update` or `try_update`, whichever suits it. Each of them defaults
+/// to the other one, so at least one must be implemented.
 pub trait Widget {
     /// The function used to update the widget.
     ///
     /// Note: even with no update, the widget should return an non-empty update with empty data
     /// payload.
     /// If None is returned, the framework will disable this widget and do not call the update
-    /// function anymore.
-    fn update(&mut self) -> Option<WidgetUpdate>;
+    /// function anymore. To hide the widget only for now, use `WidgetData::Hidden` instead.
+    ///
+    /// The default implementation calls `try_update`, and treats an error as no update.
+    fn update(&mut self) -> Option<WidgetUpdate> {
+        self.try_update().ok().flatten()
+    }
+
+    /// The fallible version of the update function, which is what the framework actually calls.
+    ///
+    /// When an error is returned, the widget isn't disabled. Instead, the framework shows the
+    /// error block in place of the widget and retries later with an exponential backoff.
+    ///
+    /// The default implementation just calls `update`.
+    fn try_update(&mut self) -> Result<Option<WidgetUpdate>> {
+        Ok(self.update())
+    }
+
+    /// The block shown when the update fails.
+    ///
+    /// If None is returned, the default error block of the collection is used.
+    fn error_block(&self, _error: &Error) -> Option<Block> {
+        None
+    }
+
+    /// Handle a mouse click on the block produced by this widget.
+    ///
+    /// This is only called when the click events are enabled in the protocol header. The framework
+    /// updates the widget right after the handler returns, so the change is visible immediately.
+    ///
+    /// The clicks are routed by the name and instance of the block. If the widget doesn't name its
+    /// block, the framework assigns a unique name to it.
+    fn on_click(&mut self, _event: &ClickEvent) {}
+
+    /// Receive the notifier of this widget.
+    ///
+    /// This is called once when the widget is pushed to the collection. An event-driven widget
+    /// can keep the notifier and use it to request an update, instead of polling with a short
+    /// refresh interval.
+    fn set_notifier(&mut self, _notifier: Notifier) {}
+}
+
+/// The handle that wakes up the update loop for a widget
+///
+/// The notifier can be cloned and sent to any thread. Calling `notify` makes the framework update
+/// the widget and redraw the bar right away.
+#[derive(Clone)]
+pub struct Notifier {
+    id: usize,
+    message_tx: Sender<Message>,
+}
+
+impl Notifier {
+    /// Mark the widget dirty, so that it will be updated as soon as possible
+    pub fn notify(&self) {
+        self.message_tx.send(Message::Notify(self.id)).ok();
+    }
 }
 
 /// The trait for a decoratable object
@@ -65,20 +198,55 @@ pub trait Decoratable: Widget + Sized {
 impl<T: Widget + Sized> Decoratable for T {}
 
 impl<T: Widget, F: FnMut(&mut Block)> Widget for WidgetDecorator<T, F> {
-    fn update(&mut self) -> Option<WidgetUpdate> {
-        if let Some(mut inner_result) = self.inner.update() {
+    fn try_update(&mut self) -> Result<Option<WidgetUpdate>> {
+        if let Some(mut inner_result) = self.inner.try_update()? {
             if let Some(ref mut data) = inner_result.data {
-                (self.proc)(data);
+                data.blocks_mut().iter_mut().for_each(&mut self.proc);
             }
 
-            return Some(inner_result);
+            return Ok(Some(inner_result));
         }
-        None
+        Ok(None)
+    }
+
+    fn error_block(&self, error: &Error) -> Option<Block> {
+        self.inner.error_block(error)
+    }
+
+    fn on_click(&mut self, event: &ClickEvent) {
+        self.inner.on_click(event)
+    }
+
+    fn set_notifier(&mut self, notifier: Notifier) {
+        self.inner.set_notifier(notifier)
+    }
+}
+
+/// A boxed widget is a widget as well, so that a widget picked at runtime can be pushed
+impl<W: Widget + ?Sized> Widget for Box<W> {
+    fn update(&mut self) -> Option<WidgetUpdate> {
+        (**self).update()
+    }
+
+    fn try_update(&mut self) -> Result<Option<WidgetUpdate>> {
+        (**self).try_update()
+    }
+
+    fn error_block(&self, error: &Error) -> Option<Block> {
+        (**self).error_block(error)
+    }
+
+    fn on_click(&mut self, event: &ClickEvent) {
+        (**self).on_click(event)
+    }
+
+    fn set_notifier(&mut self, notifier: Notifier) {
+        (**self).set_notifier(notifier)
     }
 }
 
 #[derive(PartialEq, Eq)]
-struct RefreshEvent(SystemTime, usize);
+struct RefreshEvent(Instant, usize);
 
 impl PartialOrd for RefreshEvent {
     fn partial_cmp(&self, that: &Self) -> Option<Ordering> {
@@ -92,6 +260,131 @@ impl Ord for RefreshEvent {
     }
 }
 
+/// The outcome of a single widget update
+pub(crate) enum UpdateResult {
+    /// The widget is updated, None indicates the widget should be disabled
+    Updated(Option<WidgetUpdate>),
+    /// The update failed, with the error block provided by the widget
+    Failed(Option<Block>),
+}
+
+impl UpdateResult {
+    /// Run the update of the widget
+    ///
+    /// A panic in the widget is turned into an error, so that a single broken widget can't take
+    /// down the whole bar.
+    pub(crate) fn of<W: Widget + ?Sized>(widget: &mut W) -> Self {
+        let result = catch_unwind(AssertUnwindSafe(|| widget.try_update()))
+            .unwrap_or_else(|payload| Err(Error::from_panic(payload)));
+
+        match result {
+            Ok(update) => UpdateResult::Updated(update),
+            Err(error) => {
+                let block = catch_unwind(AssertUnwindSafe(|| widget.error_block(&error)));
+                UpdateResult::Failed(block.unwrap_or(None))
+            }
+        }
+    }
+}
+
+/// Pass the click event to the widget, the panic in the click handler is ignored
+pub(crate) fn click_widget<W: Widget + ?Sized>(widget: &mut W, event: &ClickEvent) {
+    catch_unwind(AssertUnwindSafe(|| widget.on_click(event))).ok();
+}
+
+/// The time until the next multiple of the interval since the UNIX epoch
+fn until_aligned(interval: Duration) -> Duration {
+    let interval = interval.as_nanos();
+    if interval == 0 {
+        return Duration::new(0, 0);
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+
+    let wait = interval - now % interval;
+    Duration::new((wait / 1_000_000_000) as u64, (wait % 1_000_000_000) as u32)
+}
+
+/// The messages delivered to the update loop from other threads
+pub(crate) enum Message {
+    /// The user clicked a block
+    Click(ClickEvent),
+    /// A widget running on a worker thread finished its update
+    Updated(usize, UpdateResult),
+    /// A widget asked for an update through its notifier
+    Notify(usize),
+    /// The program received a signal
+    Signal(i32),
+    /// The widget set is changed through a control handle
+    Control(Command),
+}
+
+/// The number of the realtime signal `SIGRTMIN+offset`, if there's such a signal
+pub(crate) fn realtime_signal(offset: u32) -> Result<i32> {
+    i32::try_from(offset)
+        .ok()
+        .and_then(|offset| libc::SIGRTMIN().checked_add(offset))
+        .filter(|signo| *signo <= libc::SIGRTMAX())
+        .ok_or_else(|| Error::Other(format!("No such realtime signal: SIGRTMIN+{}", offset)))
+}
+
+/// How a widget in the collection gets updated
+enum Runner {
+    /// The widget is updated on the main thread
+    Local(Box<dyn Widget>),
+    /// The widget is updated on its own thread
+    Threaded(Worker),
+}
+
+/// A widget in the collection along with its state in the update loop
+struct Slot {
+    /// The unique id of the widget, which is never reused even if the widget is removed
+    id: usize,
+    /// The name used to look up the widget, and the default name of its blocks
+    name: String,
+    runner: Runner,
+    /// The realtime signal that updates the widget immediately
+    signal: Option<i32>,
+    /// When the widget should be updated next, None if the widget is disabled
+    next_refresh: Option<Instant>,
+    /// If the widget is refreshed on the wall clock, so it's never updated ahead of time
+    aligned: bool,
+    /// The blocks currently drawn for the widget
+    blocks: Vec<Block>,
+    /// How many times in a row the update has failed
+    failures: u32,
+    /// If the stale marker is shown in place of the widget, until its pending update finishes
+    stale: bool,
+    /// The block shown in place of the widget, and when it expires
+    overridden: Option<(Block, Option<Instant>)>,
+    /// Identifies the configuration the widget is created from, see `LayoutEntry`
+    key: Option<String>,
+}
+
+/// A widget of the layout built from a configuration
+pub(crate) struct LayoutEntry {
+    /// Identifies the configuration of the widget, which changes whenever the configuration does
+    pub(crate) key: String,
+    pub(crate) name: Option<String>,
+    pub(crate) signal: Option<i32>,
+    /// The widget created from the configuration, None to keep the existing widget with the key
+    pub(crate) widget: Option<LayoutWidget>,
+}
+
+/// How a widget of the layout is updated
+pub(crate) enum LayoutWidget {
+    /// The widget is updated on the main thread
+    Local(Box<dyn Widget>),
+    /// The widget is updated on its own thread, with the timeout of each update
+    Threaded(Box<dyn Widget + Send>, Duration),
+}
+
+/// Builds the layout from the configuration again, given the keys of the existing widgets
+type Reloader = Box<dyn FnMut(&[String]) -> Result<Vec<LayoutEntry>>>;
+
 /// The collection of widgets
 ///
 /// in **i3monkit** a status bar is abstracted as an widget collection.
@@ -108,75 +401,954 @@ impl Ord for RefreshEvent {
 ///     bar.update_loop();
 /// ```
 pub struct WidgetCollection {
-    widgets: Vec<Box<dyn Widget>>,
-    idx_map: Vec<usize>,
+    widgets: Vec<Slot>,
+    next_id: usize,
     event_queue: BinaryHeap<RefreshEvent>,
-    result_buffer: Vec<Block>,
+    last_drawn: Option<Vec<Block>>,
+    batch_window: Duration,
+    stale_block: Block,
+    error_block: Block,
+    message_tx: Sender<Message>,
+    message_rx: Receiver<Message>,
+    stop_signal: i32,
+    cont_signal: i32,
+    paused: bool,
+    /// Shared with every control handle, to tell if any of them is still alive
+    handles: Arc<()>,
+    reloader: Option<Reloader>,
+    ipc_server: Option<ipc::Server>,
+    /// Set when the program is asked to terminate, which ends the update loop
+    exiting: bool,
 }
 
 impl WidgetCollection {
     /// Creates a new widget collection
     pub fn new() -> WidgetCollection {
+        let (message_tx, message_rx) = channel();
         WidgetCollection {
             widgets: Vec::new(),
+            next_id: 0,
             event_queue: BinaryHeap::new(),
-            result_buffer: Vec::new(),
-            idx_map: Vec::new(),
+            last_drawn: None,
+            batch_window: DEFAULT_BATCH_WINDOW,
+            stale_block: Block::new()
+                .append_full_text("stale")
+                .color(ColorRGB(0x77, 0x77, 0x77))
+                .clone(),
+            error_block: Block::new()
+                .append_full_text("ERR")
+                .color(ColorRGB::red())
+                .clone(),
+            message_tx,
+            message_rx,
+            stop_signal: libc::SIGSTOP,
+            cont_signal: libc::SIGCONT,
+            paused: false,
+            handles: Arc::new(()),
+            reloader: None,
+            ipc_server: None,
+            exiting: false,
+        }
+    }
+
+    /// Add a widget at the given position and returns the position it ends up at
+    ///
+    /// **make_runner** Creates the runner of the widget from its notifier
+    fn insert<F: FnOnce(Notifier) -> Runner>(
+        &mut self,
+        position: usize,
+        name: Option<String>,
+        signal: Option<i32>,
+        make_runner: F,
+    ) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let notifier = Notifier {
+            id,
+            message_tx: self.message_tx.clone(),
+        };
+
+        let position = position.min(self.widgets.len());
+        self.widgets.insert(
+            position,
+            Slot {
+                id,
+                name: name.unwrap_or_else(|| id.to_string()),
+                runner: make_runner(notifier),
+                signal,
+                next_refresh: None,
+                aligned: false,
+                blocks: Vec::new(),
+                failures: 0,
+                stale: false,
+                overridden: None,
+                key: None,
+            },
+        );
+        position
+    }
+
+    /// Replace the widgets with the layout and returns the positions of the newly created widgets
+    ///
+    /// An entry without a widget takes over the existing widget with the same key, along with its
+    /// state, so that the widget doesn't start over.
+    pub(crate) fn set_layout(&mut self, layout: Vec<LayoutEntry>) -> Vec<usize> {
+        let mut existing: Vec<_> = std::mem::take(&mut self.widgets)
+            .into_iter()
+            .map(Some)
+            .collect();
+        let mut created = Vec::new();
+
+        for entry in layout {
+            match entry.widget {
+                Some(widget) => {
+                    let message_tx = self.message_tx.clone();
+                    let make_runner = |notifier: Notifier| match widget {
+                        LayoutWidget::Local(mut widget) => {
+                            widget.set_notifier(notifier);
+                            Runner::Local(widget)
+                        }
+                        LayoutWidget::Threaded(mut widget, timeout) => {
+                            let id = notifier.id;
+                            widget.set_notifier(notifier);
+                            Runner::Threaded(Worker::spawn(widget, id, timeout, message_tx))
+                        }
+                    };
+                    let pos = self.insert(usize::MAX, entry.name, entry.signal, make_runner);
+                    self.widgets[pos].key = Some(entry.key);
+                    created.push(pos);
+                }
+                None => {
+                    let slot = existing
+                        .iter_mut()
+                        .find(|slot| matches!(slot, Some(slot) if slot.key.as_ref() == Some(&entry.key)))
+                        .and_then(Option::take);
+                    self.widgets.extend(slot);
+                }
+            }
         }
+
+        created
+    }
+
+    /// Set the function that builds the layout again when the bar is reloaded
+    pub(crate) fn set_reloader<F>(&mut self, reloader: F)
+    where
+        F: FnMut(&[String]) -> Result<Vec<LayoutEntry>> + 'static,
+    {
+        self.reloader = Some(Box::new(reloader));
+    }
+
+    fn push_local<W: Widget + 'static>(
+        &mut self,
+        name: Option<String>,
+        signal: Option<i32>,
+        mut widget: W,
+    ) -> &mut Self {
+        self.insert(usize::MAX, name, signal, |notifier| {
+            widget.set_notifier(notifier);
+            Runner::Local(Box::new(widget))
+        });
+        self
     }
 
     /// Push a new widget to the collection
+    ///
+    /// The widget is named after the order it's pushed, starting from "0".
     pub fn push<W: Widget + 'static>(&mut self, widget: W) -> &mut Self {
-        self.widgets.push(Box::new(widget));
+        self.push_local(None, None, widget)
+    }
+
+    /// Push a new widget with a name to the collection
+    ///
+    /// The name is used to refer to the widget through a control handle. It's also the default name
+    /// of the blocks of the widget.
+    ///
+    /// **name** The name of the widget
+    /// **widget** The widget to push
+    pub fn push_named<W: Widget + 'static>(&mut self, name: &str, widget: W) -> &mut Self {
+        self.push_local(Some(name.to_string()), None, widget)
+    }
+
+    /// Push a new widget that is updated on its own thread
+    ///
+    /// A slow update of this widget doesn't block any other widget. While the update is running,
+    /// the last block of the widget stays on the bar. If the update takes longer than the timeout,
+    /// the block is replaced with the stale marker until the update finishes.
+    ///
+    /// **widget** The widget to push
+    /// **timeout** How long an update may take before the widget is considered stale
+    pub fn push_threaded<W: Widget + Send + 'static>(
+        &mut self,
+        mut widget: W,
+        timeout: Duration,
+    ) -> &mut Self {
+        let message_tx = self.message_tx.clone();
+        self.insert(usize::MAX, None, None, |notifier| {
+            let id = notifier.id;
+            widget.set_notifier(notifier);
+            Runner::Threaded(Worker::spawn(widget, id, timeout, message_tx))
+        });
         self
     }
 
-    /// Start the main update loop and drawing the wigets on the i3bar
-    pub fn update_loop<T: Write>(&mut self, mut proto_inst: I3Protocol<T>) {
-        self.event_queue.clear();
+    /// Push a new widget that is updated immediately when the program receives `SIGRTMIN+signal`
+    ///
+    /// This works like the signals of i3blocks, for example, with the signal 3, running
+    /// `pkill -RTMIN+3 your-bar` updates the widget without waiting for its refresh interval.
+    /// Multiple widgets can share the same signal.
+    ///
+    /// An error is returned if there's no such realtime signal.
+    ///
+    /// **widget** The widget to push
+    /// **signal** The offset of the realtime signal
+    pub fn push_with_signal<W: Widget + 'static>(
+        &mut self,
+        widget: W,
+        signal: u32,
+    ) -> Result<&mut Self> {
+        let signo = realtime_signal(signal)?;
+        Ok(self.push_local(None, Some(signo), widget))
+    }
+
+    /// Get a handle that changes the widgets while the bar is running
+    pub fn control_handle(&self) -> ControlHandle {
+        ControlHandle::new(self.message_tx.clone(), self.handles.clone())
+    }
+
+    /// Start the control server on `$XDG_RUNTIME_DIR/i3monkit.sock`
+    ///
+    /// The running bar can then be driven by the `i3monkit-msg` program, see the `ipc` module for
+    /// the commands. The socket is removed once the collection is dropped.
+    pub fn enable_ipc(&mut self) -> std::io::Result<&mut Self> {
+        self.enable_ipc_at(ipc::default_socket_path())
+    }
+
+    /// Start the control server on the given socket path
+    pub fn enable_ipc_at<P: AsRef<Path>>(&mut self, path: P) -> std::io::Result<&mut Self> {
+        self.ipc_server = Some(ipc::serve(path.as_ref(), self.control_handle())?);
+        Ok(self)
+    }
+
+    /// Set the block shown in place of a threaded widget whose update has timed out
+    pub fn set_stale_block(&mut self, block: Block) -> &mut Self {
+        self.stale_block = block;
+        self
+    }
+
+    /// Set the block shown in place of a widget whose update has failed
+    ///
+    /// A widget can override this by providing its own error block.
+    pub fn set_error_block(&mut self, block: Block) -> &mut Self {
+        self.error_block = block;
+        self
+    }
 
-        let size = self.widgets.len();
+    /// Set the window in which the due widgets are updated together
+    ///
+    /// All the widgets due within the window are updated at once and the bar is redrawn only once
+    /// for all of them. The widgets aligned to the wall clock are never updated ahead of their
+    /// time though.
+    pub fn set_batch_window(&mut self, window: Duration) -> &mut Self {
+        self.batch_window = window;
+        self
+    }
 
-        for (idx, widget) in self.widgets.iter_mut().enumerate() {
-            if let Some(result) = widget.update() {
-                self.event_queue.push(RefreshEvent(
-                    SystemTime::now() + result.refresh_interval,
-                    idx,
-                ));
-                if let Some(data) = result.data {
-                    self.result_buffer.push(data);
+    /// The current position of the widget with the id
+    fn position(&self, id: usize) -> Option<usize> {
+        self.widgets.iter().position(|slot| slot.id == id)
+    }
+
+    /// The current position of the first widget with the name
+    fn find(&self, name: &str) -> Option<usize> {
+        self.widgets.iter().position(|slot| slot.name == name)
+    }
+
+    fn schedule(&mut self, pos: usize, interval: Duration) {
+        let next = Instant::now() + interval;
+        let slot = &mut self.widgets[pos];
+        slot.next_refresh = Some(next);
+        self.event_queue.push(RefreshEvent(next, slot.id));
+    }
+
+    /// Drop the events of the widgets that have been updated, disabled or removed since the event
+    /// was scheduled, and returns the time of the next valid event
+    fn next_deadline(&mut self) -> Option<Instant> {
+        while let Some(event) = self.event_queue.peek() {
+            let next_refresh = self
+                .position(event.1)
+                .and_then(|pos| self.widgets[pos].next_refresh);
+            if next_refresh == Some(event.0) {
+                return Some(event.0);
+            }
+            self.event_queue.pop();
+        }
+        None
+    }
+
+    /// All the blocks on the bar, in the order of the widgets
+    fn blocks(&self) -> Vec<Block> {
+        let mut blocks = Vec::new();
+        for slot in self.widgets.iter() {
+            match slot.overridden {
+                Some((ref block, _)) => blocks.push(block.clone()),
+                None if slot.stale => {
+                    let mut block = self.stale_block.clone();
+                    block.default_name(&slot.name);
+                    blocks.push(block);
                 }
-                self.idx_map.push(self.result_buffer.len() - 1);
+                None => blocks.extend(slot.blocks.iter().cloned()),
+            }
+        }
+        blocks
+    }
+
+    /// Redraw the bar, unless nothing has changed since last time
+    fn redraw<O: BarOutput>(&mut self, output: &mut O) {
+        let blocks = self.blocks();
+        if self.last_drawn.as_ref() != Some(&blocks) {
+            output.refresh(&blocks);
+            self.last_drawn = Some(blocks);
+        }
+    }
+
+    /// When the first override of the widgets expires
+    fn next_expiry(&self) -> Option<Instant> {
+        self.widgets
+            .iter()
+            .filter_map(|slot| slot.overridden.as_ref().and_then(|(_, until)| *until))
+            .min()
+    }
+
+    /// Clear the overrides that have expired and returns if the bar needs to be redrawn
+    fn expire_overrides(&mut self) -> bool {
+        let now = Instant::now();
+        let mut redraw = false;
+        for slot in self.widgets.iter_mut() {
+            if matches!(slot.overridden, Some((_, Some(until))) if until <= now) {
+                slot.overridden = None;
+                redraw = true;
+            }
+        }
+        redraw
+    }
+
+    fn has_pending_update(&self) -> bool {
+        self.widgets.iter().any(|slot| match slot.runner {
+            Runner::Threaded(ref worker) => worker.busy,
+            Runner::Local(_) => false,
+        })
+    }
+
+    /// Set the blocks of the widget, naming the blocks after the widget if they have no name
+    fn set_blocks(&mut self, pos: usize, mut blocks: Vec<Block>) {
+        let slot = &mut self.widgets[pos];
+        for block in blocks.iter_mut() {
+            block.default_name(&slot.name);
+        }
+        slot.blocks = blocks;
+    }
+
+    /// Apply the update returned by the widget and returns if the bar needs to be redrawn
+    fn apply_update(&mut self, pos: usize, result: UpdateResult) -> bool {
+        let update = match result {
+            UpdateResult::Updated(update) => update,
+            UpdateResult::Failed(block) => {
+                let block = block.unwrap_or_else(|| self.error_block.clone());
+                self.set_blocks(pos, vec![block]);
+
+                let failures = &mut self.widgets[pos].failures;
+                let delay = INITIAL_RETRY_DELAY * 2u32.pow((*failures).min(16));
+                *failures += 1;
+                self.schedule(pos, delay.min(MAX_RETRY_DELAY));
+
+                return true;
+            }
+        };
+
+        self.widgets[pos].failures = 0;
+
+        if let Some(update) = update {
+            if let Some(data) = update.data {
+                self.set_blocks(pos, data.into_blocks());
+            }
+
+            let interval = if update.aligned {
+                until_aligned(update.refresh_interval)
             } else {
-                self.idx_map.push(size);
+                update.refresh_interval
+            };
+            self.widgets[pos].aligned = update.aligned;
+            self.schedule(pos, interval);
+
+            return true;
+        }
+
+        self.widgets[pos].next_refresh = None;
+        false
+    }
+
+    /// Update the widget and returns if the bar needs to be redrawn
+    fn update_widget(&mut self, pos: usize) -> bool {
+        match self.widgets[pos].runner {
+            Runner::Local(ref mut widget) => {
+                let result = UpdateResult::of(widget.as_mut());
+                self.apply_update(pos, result)
+            }
+            Runner::Threaded(ref worker) if worker.busy => {
+                self.widgets[pos].stale = true;
+                true
+            }
+            Runner::Threaded(ref mut worker) => {
+                worker.request_update();
+                let timeout = worker.timeout;
+                self.schedule(pos, timeout);
+                false
+            }
+        }
+    }
+
+    /// Update the widget without waiting for its timer and returns if the bar needs to be redrawn
+    fn update_now(&mut self, pos: usize) -> bool {
+        let slot = &self.widgets[pos];
+        match slot.runner {
+            // The pending update will be delivered soon
+            Runner::Threaded(ref worker) if worker.busy => false,
+            _ if slot.next_refresh.is_some() => self.update_widget(pos),
+            _ => false,
+        }
+    }
+
+    /// Pass the click event to the widget owns the clicked block and returns if the bar needs to
+    /// be redrawn
+    fn dispatch_click(&mut self, event: &ClickEvent) -> bool {
+        let clicked = self.widgets.iter().position(|slot| {
+            slot.next_refresh.is_some() && slot.blocks.iter().any(|block| block.is_clicked(event))
+        });
+
+        match clicked {
+            Some(pos) => self.click(pos, event),
+            None => false,
+        }
+    }
+
+    /// Pass the click event to the widget and returns if the bar needs to be redrawn
+    fn click(&mut self, pos: usize, event: &ClickEvent) -> bool {
+        match self.widgets[pos].runner {
+            Runner::Local(ref mut widget) => {
+                click_widget(widget.as_mut(), event);
+                self.update_widget(pos)
+            }
+            Runner::Threaded(ref mut worker) => {
+                worker.click(event);
+                let timeout = worker.timeout;
+                self.schedule(pos, timeout);
+                false
             }
         }
+    }
 
-        while !self.event_queue.is_empty() {
-            let next_event = self.event_queue.pop().unwrap();
+    /// Change the widget set as requested and returns if the bar needs to be redrawn
+    fn apply_command(&mut self, command: Command) -> bool {
+        match command {
+            Command::Insert(position, name, mut widget) => {
+                let pos = self.insert(position, Some(name), None, |notifier| {
+                    widget.set_notifier(notifier);
+                    Runner::Local(widget)
+                });
+                self.update_widget(pos)
+            }
+            Command::Remove(name) => match self.find(&name) {
+                Some(pos) => {
+                    self.widgets.remove(pos);
+                    true
+                }
+                None => false,
+            },
+            Command::Move(name, position) => match self.find(&name) {
+                Some(pos) => {
+                    let slot = self.widgets.remove(pos);
+                    let position = position.min(self.widgets.len());
+                    self.widgets.insert(position, slot);
+                    true
+                }
+                None => false,
+            },
+            Command::Swap(first, second) => match (self.find(&first), self.find(&second)) {
+                (Some(first), Some(second)) => {
+                    self.widgets.swap(first, second);
+                    true
+                }
+                _ => false,
+            },
+            // The new widget takes the place, the name and the signal of the old one, but not
+            // its state in the update loop
+            Command::Replace(name, mut widget) => match self.find(&name) {
+                Some(pos) => {
+                    let slot = self.widgets.remove(pos);
+                    let pos = self.insert(pos, Some(slot.name), slot.signal, |notifier| {
+                        widget.set_notifier(notifier);
+                        Runner::Local(widget)
+                    });
+                    self.update_widget(pos);
+                    true
+                }
+                None => false,
+            },
+            Command::Refresh(name) => match self.find(&name) {
+                Some(pos) => self.update_now(pos),
+                None => false,
+            },
+            Command::Click(name, button) => match self.find(&name) {
+                Some(pos) => {
+                    let slot = &self.widgets[pos];
+                    let event = match slot.blocks.first() {
+                        Some(block) => block.click_event(button),
+                        None => Block::new().name(&slot.name).click_event(button),
+                    };
+                    self.click(pos, &event)
+                }
+                None => false,
+            },
+            Command::ClickBlock(event) => self.dispatch_click(&event),
+            Command::Override(name, text) => match self.find(&name) {
+                Some(pos) => {
+                    let slot = &mut self.widgets[pos];
+                    slot.overridden = text.map(|(text, duration)| {
+                        let mut block = Block::new();
+                        block.name(&slot.name).full_text(&text);
+                        (block, duration.map(|duration| Instant::now() + duration))
+                    });
+                    true
+                }
+                None => false,
+            },
+            Command::Pause => {
+                self.paused = true;
+                false
+            }
+            Command::Resume => {
+                self.paused = false;
+                self.resync()
+            }
+            Command::Reload => self.reload(),
+            Command::List(reply_tx) => {
+                let widgets = self
+                    .widgets
+                    .iter()
+                    .map(|slot| (slot.name.clone(), slot.blocks.clone()))
+                    .collect();
+                reply_tx.send(widgets).ok();
+                false
+            }
+        }
+    }
 
-            let sleep_duration = next_event
-                .0
-                .duration_since(SystemTime::now())
-                .unwrap_or_else(|_| Duration::new(0, 0));
+    /// Build the widgets from the configuration again and returns if the bar needs to be redrawn
+    ///
+    /// If the configuration is broken, the widgets are kept as is.
+    fn reload(&mut self) -> bool {
+        let mut reloader = match self.reloader.take() {
+            Some(reloader) => reloader,
+            None => return false,
+        };
 
-            sleep(sleep_duration);
+        let keys: Vec<_> = self
+            .widgets
+            .iter()
+            .filter_map(|slot| slot.key.clone())
+            .collect();
+        // A widget may panic while it's created, which shouldn't take the running bar down
+        let result = catch_unwind(AssertUnwindSafe(|| reloader(&keys)))
+            .unwrap_or_else(|payload| Err(Error::from_panic(payload)));
+        self.reloader = Some(reloader);
 
-            if let Some(mut update) = self.widgets[next_event.1].update() {
-                if update.data.is_some() {
-                    std::mem::swap(
-                        &mut self.result_buffer[self.idx_map[next_event.1]],
-                        update.data.as_mut().unwrap(),
-                    );
+        match result {
+            Ok(layout) => {
+                for pos in self.set_layout(layout) {
+                    self.update_widget(pos);
                 }
 
-                let new_event =
-                    RefreshEvent(SystemTime::now() + update.refresh_interval, next_event.1);
+                let signals: Vec<_> = self.widgets.iter().filter_map(|slot| slot.signal).collect();
+                forward_signals(&signals, self.message_tx.clone()).ok();
 
-                self.event_queue.push(new_event);
+                true
+            }
+            Err(e) => {
+                eprintln!("i3monkit: Failed to reload the configuration: {}", e);
+                false
+            }
+        }
+    }
 
-                proto_inst.refresh(&self.result_buffer)
+    /// Handle the message from other threads and returns if the bar needs to be redrawn
+    fn handle_message(&mut self, message: Message) -> bool {
+        match message {
+            Message::Click(event) => self.dispatch_click(&event),
+            // The widget may have been removed in the meantime
+            Message::Updated(id, result) => match self.position(id) {
+                Some(pos) => {
+                    let slot = &mut self.widgets[pos];
+                    if let Runner::Threaded(ref mut worker) = slot.runner {
+                        worker.busy = false;
+                    }
+                    // The marker goes away even if the update keeps the blocks as they were
+                    let was_stale = std::mem::replace(&mut slot.stale, false);
+                    self.apply_update(pos, result) || was_stale
+                }
+                None => false,
+            },
+            Message::Control(command) => self.apply_command(command),
+            // The widget will be updated when the bar is shown again
+            Message::Notify(_) if self.paused => false,
+            Message::Notify(id) => match self.position(id) {
+                Some(pos) => self.update_now(pos),
+                None => false,
+            },
+            Message::Signal(signo) if signo == self.stop_signal => {
+                self.paused = true;
+                false
+            }
+            Message::Signal(signo) if signo == self.cont_signal => {
+                self.paused = false;
+                self.resync()
+            }
+            Message::Signal(libc::SIGHUP) if self.reloader.is_some() => self.reload(),
+            Message::Signal(libc::SIGTERM) | Message::Signal(libc::SIGINT)
+                if self.ipc_server.is_some() =>
+            {
+                self.exiting = true;
+                false
+            }
+            Message::Signal(_) if self.paused => false,
+            Message::Signal(signo) => {
+                let mut redraw = false;
+                for pos in 0..self.widgets.len() {
+                    if self.widgets[pos].signal == Some(signo) {
+                        redraw |= self.update_now(pos);
+                    }
+                }
+                redraw
             }
         }
     }
+
+    /// Update every widget at once, for example, after the bar is shown again
+    fn resync(&mut self) -> bool {
+        let mut redraw = false;
+        for pos in 0..self.widgets.len() {
+            redraw |= self.update_now(pos);
+        }
+        redraw
+    }
+
+    /// Start updating every widget from scratch
+    fn update_all(&mut self) {
+        self.event_queue.clear();
+
+        for pos in 0..self.widgets.len() {
+            let slot = &mut self.widgets[pos];
+            slot.next_refresh = None;
+            slot.blocks.clear();
+            slot.failures = 0;
+
+            self.update_widget(pos);
+        }
+    }
+
+    /// Update every widget once and draw the bar, instead of running the update loop
+    ///
+    /// This is meant for the outputs read by another program from time to time, such as the tmux
+    /// status line. The widgets pushed with `push_threaded` are waited for up to their timeout, and
+    /// the event-driven widgets only show what they have got by then.
+    pub fn draw_once<O: BarOutput>(&mut self, mut output: O) {
+        self.update_all();
+
+        let deadline = self
+            .widgets
+            .iter()
+            .filter_map(|slot| match slot.runner {
+                Runner::Threaded(ref worker) => Some(Instant::now() + worker.timeout),
+                Runner::Local(_) => None,
+            })
+            .max();
+
+        if let Some(deadline) = deadline {
+            while self.has_pending_update() {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                match self.message_rx.recv_timeout(timeout) {
+                    Ok(message) => self.handle_message(message),
+                    Err(_) => break,
+                };
+            }
+        }
+
+        output.refresh(&self.blocks());
+    }
+
+    /// Start the main update loop and drawing the wigets on the i3bar
+    ///
+    /// The bar is drawn to any `BarOutput`, which is usually an `I3Protocol`. The loop keeps
+    /// running as long as any widget is active, or any control handle is alive. When the control
+    /// server is started, SIGTERM and SIGINT end the loop as well.
+    pub fn update_loop<O: BarOutput>(&mut self, mut output: O) {
+        self.update_all();
+        self.redraw(&mut output);
+
+        output.start_clicks(ClickSender::new(self.message_tx.clone()));
+
+        self.stop_signal = output.stop_signal() as i32;
+        self.cont_signal = output.cont_signal() as i32;
+
+        // SIGSTOP can't be caught, i3bar just freezes the program in this case
+        let mut signals = vec![self.cont_signal];
+        if self.stop_signal != libc::SIGSTOP {
+            signals.push(self.stop_signal);
+        }
+        if self.reloader.is_some() {
+            signals.push(libc::SIGHUP);
+        }
+        // Leave the loop on termination, so that the socket of the control server is removed
+        if self.ipc_server.is_some() {
+            signals.extend([libc::SIGTERM, libc::SIGINT].iter());
+        }
+        signals.extend(self.widgets.iter().filter_map(|slot| slot.signal));
+        forward_signals(&signals, self.message_tx.clone()).ok();
+
+        let mut suspended = suspended_time();
+
+        while !self.exiting {
+            // While the bar is hidden, nothing is updated until it's shown again
+            if self.paused {
+                if let Ok(message) = self.message_rx.recv() {
+                    self.handle_message(message);
+                    if !self.paused {
+                        self.redraw(&mut output);
+                    }
+                }
+                continue;
+            }
+
+            if self.expire_overrides() {
+                self.redraw(&mut output);
+            }
+
+            // All the blocks are outdated after the system is resumed from suspension
+            let last_suspended = std::mem::replace(&mut suspended, suspended_time());
+            if suspended > last_suspended + MIN_SUSPENSION {
+                if self.resync() {
+                    self.redraw(&mut output);
+                }
+                continue;
+            }
+
+            let message = match self.next_deadline() {
+                Some(deadline) => {
+                    let wakeup = self
+                        .next_expiry()
+                        .map_or(deadline, |expiry| expiry.min(deadline));
+                    let timeout = wakeup.saturating_duration_since(Instant::now());
+                    match self.message_rx.recv_timeout(timeout.min(MAX_SLEEP)) {
+                        Ok(message) => Some(message),
+                        Err(_) if Instant::now() < deadline => continue,
+                        Err(_) => None,
+                    }
+                }
+                None if self.has_pending_update() || Arc::strong_count(&self.handles) > 1 => {
+                    match self.next_expiry() {
+                        Some(expiry) => {
+                            let timeout = expiry.saturating_duration_since(Instant::now());
+                            match self.message_rx.recv_timeout(timeout) {
+                                Ok(message) => Some(message),
+                                Err(_) => continue,
+                            }
+                        }
+                        None => self.message_rx.recv().ok(),
+                    }
+                }
+                None => break,
+            };
+
+            if let Some(message) = message {
+                let mut redraw = self.handle_message(message);
+                while let Ok(message) = self.message_rx.try_recv() {
+                    redraw |= self.handle_message(message);
+                }
+                if redraw {
+                    self.redraw(&mut output);
+                }
+                continue;
+            }
+
+            // Update all the widgets due in the batch window, then redraw the bar only once. The
+            // widgets aligned to the wall clock wait for their time, or a clock would show the
+            // previous second.
+            let now = Instant::now();
+            let horizon = now + self.batch_window;
+            let mut due = Vec::new();
+            let mut early = Vec::new();
+            while let Some(deadline) = self.next_deadline() {
+                if deadline > horizon {
+                    break;
+                }
+                let event = self.event_queue.pop().unwrap();
+                match self.position(event.1) {
+                    Some(pos) if deadline > now && self.widgets[pos].aligned => early.push(event),
+                    _ => due.push(event.1),
+                }
+            }
+            self.event_queue.extend(early);
+
+            let mut redraw = false;
+            for id in due {
+                if let Some(pos) = self.position(id) {
+                    redraw |= self.update_widget(pos);
+                }
+            }
+
+            if redraw {
+                self.redraw(&mut output);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// A widget playing back the data of its updates, which disables itself after the last one
+    ///
+    /// None keeps the blocks on the bar as they are.
+    struct Script(VecDeque<Option<WidgetData>>);
+
+    impl Widget for Script {
+        fn update(&mut self) -> Option<WidgetUpdate> {
+            let data = self.0.pop_front()?;
+            Some(WidgetUpdate {
+                data,
+                ..WidgetUpdate::default()
+            })
+        }
+    }
+
+    fn script<I: IntoIterator<Item = Option<WidgetData>>>(updates: I) -> Script {
+        Script(updates.into_iter().collect())
+    }
+
+    fn block(text: &str) -> Block {
+        let mut block = Block::new();
+        block.full_text(text);
+        block
+    }
+
+    fn named(name: &str, text: &str) -> Block {
+        let mut block = block(text);
+        block.name(name);
+        block
+    }
+
+    /// Run the update loop until every widget is disabled, and returns what has been drawn, with
+    /// each block written as `name:text`
+    fn frames(widgets: &mut WidgetCollection) -> Vec<String> {
+        let mut frames = Vec::new();
+        widgets.update_loop(|blocks: &[Block]| {
+            let frame: Vec<_> = blocks
+                .iter()
+                .map(|block| format!("{}:{}", block.get_name(), block.get_full_text()))
+                .collect();
+            frames.push(frame.join(" "));
+        });
+        frames
+    }
+
+    #[test]
+    fn hidden_widgets_come_back() {
+        let mut widgets = WidgetCollection::new();
+        widgets.push_named(
+            "bat",
+            script(vec![
+                Some(block("50%").into()),
+                Some(WidgetData::Hidden),
+                Some(block("40%").into()),
+            ]),
+        );
+
+        assert_eq!(frames(&mut widgets), ["bat:50%", "", "bat:40%"]);
+    }
+
+    #[test]
+    fn blocks_grow_and_shrink() {
+        let mut widgets = WidgetCollection::new();
+        widgets
+            .push_named(
+                "cpu",
+                script(vec![
+                    Some(vec![block("1")].into()),
+                    Some(vec![block("1"), block("2"), block("3")].into()),
+                    Some(vec![block("4")].into()),
+                    Some(vec![].into()),
+                ]),
+            )
+            .push_named("clock", script(vec![Some(block("12:00").into())]));
+
+        assert_eq!(
+            frames(&mut widgets),
+            [
+                "cpu:1 clock:12:00",
+                "cpu:1 cpu:2 cpu:3 clock:12:00",
+                "cpu:4 clock:12:00",
+                "clock:12:00",
+            ]
+        );
+    }
+
+    #[test]
+    fn blocks_follow_the_order_of_the_widgets() {
+        let mut widgets = WidgetCollection::new();
+        widgets
+            .push_named("a", script(vec![Some(block("1").into())]))
+            .push_named(
+                "b",
+                script(vec![
+                    Some(WidgetData::Hidden),
+                    Some(vec![block("2"), block("3")].into()),
+                ]),
+            )
+            .push_named("c", script(vec![Some(block("4").into())]));
+
+        assert_eq!(frames(&mut widgets), ["a:1 c:4", "a:1 b:2 b:3 c:4"]);
+    }
+
+    #[test]
+    fn multiple_blocks_keep_their_names() {
+        let mut widgets = WidgetCollection::new();
+        widgets.push_named(
+            "disk",
+            script(vec![Some(
+                vec![named("root", "10%"), block("20%"), named("home", "30%")].into(),
+            )]),
+        );
+
+        assert_eq!(frames(&mut widgets), ["root:10% disk:20% home:30%"]);
+    }
+
+    #[test]
+    fn unchanged_blocks_are_not_redrawn() {
+        let mut widgets = WidgetCollection::new();
+        widgets.push_named(
+            "load",
+            script(vec![
+                Some(block("0.5").into()),
+                Some(block("0.5").into()),
+                None,
+                Some(vec![block("0.5")].into()),
+                Some(block("0.7").into()),
+            ]),
+        );
+
+        assert_eq!(frames(&mut widgets), ["load:0.5", "load:0.7"]);
+    }
 }
@@ -1,5 +1,5 @@
 use crate::protocol::{Block, ColorRGB};
-use crate::widget::{Widget, WidgetUpdate};
+use crate::widget::{Widget, WidgetData, WidgetUpdate};
 
 use chrono::Duration;
 
@@ -116,7 +116,10 @@ impl BatteryState {
 /// This widget shows the battery status of a laptop, such as, the percentage battery level,
 /// current status (charing, discharing, full, etc), current discharging/charing rate, estimated
 /// reminaing time, etc...
-pub struct BatteryWidget(u32);
+pub struct BatteryWidget {
+    idx: u32,
+    hide_when_full: bool,
+}
 
 impl BatteryWidget {
     /// Create a new widget for specified battery
@@ -124,11 +127,20 @@ impl BatteryWidget {
     /// **idx** The index for the battery, for most of the system with only 1 battery, it should be
     /// 0
     pub fn new(idx: u32) -> Self {
-        Self(idx)
+        Self {
+            idx,
+            hide_when_full: false,
+        }
+    }
+
+    /// Hide the widget while the battery is full, it shows up again once the battery is not full
+    pub fn hide_when_full(mut self, hide: bool) -> Self {
+        self.hide_when_full = hide;
+        self
     }
 
-    fn render_batter_status(&self) -> (String, i32) {
-        if let Some(info) = BatteryState::get(self.0) {
+    fn render_batter_status(state: Option<&BatteryState>) -> (String, i32) {
+        if let Some(info) = state {
             let mut ret = format!("{} {}%", info.stat.get_status_text(), info.percentage());
             if let Some((time, power)) = info.time_remaining() {
                 ret.push_str(&format!(
@@ -155,7 +167,24 @@ impl BatteryWidget {
 
 impl Widget for BatteryWidget {
     fn update(&mut self) -> Option<WidgetUpdate> {
-        let (msg, sev) = self.render_batter_status();
+        let state = BatteryState::get(self.idx);
+
+        let full = matches!(
+            state,
+            Some(BatteryState {
+                stat: BatteryStatus::Full,
+                ..
+            })
+        );
+        if self.hide_when_full && full {
+            return Some(WidgetUpdate {
+                refresh_interval: std::time::Duration::new(5, 0),
+                aligned: false,
+                data: Some(WidgetData::Hidden),
+            });
+        }
+
+        let (msg, sev) = Self::render_batter_status(state.as_ref());
 
         let mut data = Block::new();
 
@@ -165,6 +194,7 @@ impl Widget for BatteryWidget {
         match sev {
             0 => {
                 data.color(ColorRGB::red());
+                data.urgent(true);
             }
             1 => {
                 data.color(ColorRGB::yellow());
@@ -174,7 +204,8 @@ impl Widget for BatteryWidget {
 
         return Some(WidgetUpdate {
             refresh_interval: std::time::Duration::new(5, 0),
-            data: Some(data),
+            aligned: false,
+            data: Some(data.into()),
         });
     }
 }
@@ -0,0 +1,175 @@
+use crate::error::{Error, Result};
+use crate::protocol::{Block, ClickEvent, ColorRGB};
+use crate::widget::{Widget, WidgetData, WidgetUpdate};
+
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// The exit code of the command that makes the block urgent
+const URGENT_EXIT_CODE: i32 = 33;
+
+/// The widget that shows the output of a shell command, like the blocklets of i3blocks
+///
+/// The command is run with `sh -c` on every update, and the output follows the conventions of
+/// i3blocks:
+///
+/// - The first line is the full text of the block
+/// - The second line is the short text
+/// - The third line is the color, in `#rrggbb` format
+///
+/// The block is hidden if the output is empty. If the command exits with 33 the block is urgent,
+/// and any other non-zero exit code is an error.
+///
+/// When the block is clicked, the command is run right away with the click described by the
+/// environment variables `BLOCK_NAME`, `BLOCK_BUTTON`, `BLOCK_X` and `BLOCK_Y`, as well as
+/// `button`, `modifiers`, `x`, `y`, `relative_x`, `relative_y`, `width` and `height` used by newer
+/// versions of i3blocks. `BLOCK_INSTANCE` is always set to the instance of the widget.
+///
+/// The bar waits for the command to finish, so a slow command should be pushed with
+/// `WidgetCollection::push_threaded`.
+///
+/// ```no_run
+/// use i3monkit::widgets::CommandWidget;
+/// use i3monkit::{Header, I3Protocol, WidgetCollection};
+/// use std::time::Duration;
+///
+/// let mut bar = WidgetCollection::new();
+/// bar.push_threaded(
+///     CommandWidget::new("~/.config/i3blocks/scripts/wifi").instance("wlp58s0"),
+///     Duration::from_secs(5),
+/// );
+/// bar.update_loop(I3Protocol::new(Header::new(1), std::io::stdout()));
+/// ```
+pub struct CommandWidget {
+    command: String,
+    instance: String,
+    interval: Duration,
+    pango: bool,
+    click: Option<ClickEvent>,
+}
+
+impl CommandWidget {
+    /// Create the widget running the command every 5 seconds
+    ///
+    /// **command** The shell command to run
+    pub fn new(command: &str) -> Self {
+        Self {
+            command: command.to_string(),
+            instance: String::new(),
+            interval: Duration::from_secs(5),
+            pango: false,
+            click: None,
+        }
+    }
+
+    /// Set how often the command is run
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set the instance of the block, which is passed to the command as `BLOCK_INSTANCE`
+    pub fn instance(mut self, instance: &str) -> Self {
+        self.instance = instance.to_string();
+        self
+    }
+
+    /// Render the output of the command as Pango markup
+    pub fn use_pango(mut self) -> Self {
+        self.pango = true;
+        self
+    }
+
+    fn run(&mut self) -> Result<std::process::Output> {
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(&self.command)
+            .env("BLOCK_INSTANCE", &self.instance)
+            .stdin(Stdio::null());
+
+        if let Some(event) = self.click.take() {
+            command
+                .env("BLOCK_NAME", &event.name)
+                .env("BLOCK_BUTTON", event.button.to_string())
+                .env("BLOCK_X", event.x.to_string())
+                .env("BLOCK_Y", event.y.to_string())
+                .env("button", event.button.to_string())
+                .env("modifiers", event.modifiers.join(","))
+                .env("x", event.x.to_string())
+                .env("y", event.y.to_string())
+                .env("relative_x", event.relative_x.to_string())
+                .env("relative_y", event.relative_y.to_string())
+                .env("width", event.width.to_string())
+                .env("height", event.height.to_string());
+        }
+
+        Ok(command.output()?)
+    }
+}
+
+impl Widget for CommandWidget {
+    fn try_update(&mut self) -> Result<Option<WidgetUpdate>> {
+        let output = self.run()?;
+
+        let urgent = match output.status.code() {
+            Some(0) => false,
+            Some(URGENT_EXIT_CODE) => true,
+            _ => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                let what = match stderr.lines().next() {
+                    Some(line) if !line.is_empty() => line.to_string(),
+                    _ => output.status.to_string(),
+                };
+                return Err(Error::Other(what));
+            }
+        };
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut lines = stdout.lines();
+
+        let data = match lines.next().filter(|line| !line.is_empty()) {
+            Some(full_text) => {
+                let mut block = Block::new();
+                block
+                    .full_text(full_text)
+                    .instance(&self.instance)
+                    .urgent(urgent);
+
+                if let Some(short_text) = lines.next().filter(|line| !line.is_empty()) {
+                    block.short_text(short_text);
+                }
+
+                if let Some(color) = lines.next().and_then(|line| ColorRGB::from_str(line).ok()) {
+                    block.color(color);
+                }
+
+                if self.pango {
+                    block.use_pango();
+                }
+
+                block.into()
+            }
+            None => WidgetData::Hidden,
+        };
+
+        Ok(Some(WidgetUpdate {
+            refresh_interval: self.interval,
+            aligned: false,
+            data: Some(data),
+        }))
+    }
+
+    fn error_block(&self, error: &Error) -> Option<Block> {
+        let mut block = Block::new();
+        block.full_text(&error.to_string());
+        block.instance(&self.instance);
+        block.color(ColorRGB::red());
+        Some(block)
+    }
+
+    fn on_click(&mut self, event: &ClickEvent) {
+        self.click = Some(event.clone());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::error::Error;
 use crate::protocol::Block;
 use crate::widget::{Widget, WidgetUpdate};
 
@@ -26,12 +27,13 @@ impl CpuWidget {
         let reader = BufReader::new(file);
         for line in reader.lines() {
             let line = line?;
-            let tokens: Vec<_> = line.trim().split_whitespace().collect();
-            if tokens[0] == name {
+            let tokens: Vec<_> = line.split_whitespace().collect();
+            if tokens.len() >= 5 && tokens[0] == name {
                 let parsed = tokens[1..5]
                     .iter()
-                    .map(|x| u64::from_str_radix(x, 10).unwrap())
-                    .collect::<Vec<_>>();
+                    .map(|x| x.parse::<u64>())
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
                 return Ok((parsed[0], parsed[1], parsed[2], parsed[3]));
             }
         }
@@ -45,8 +47,19 @@ impl CpuWidget {
     /// Create a new CPU usage monitor widget for specified core
     ///
     /// **id** The core id
+    ///
+    /// Panics if the core doesn't exist, see `try_new`.
     pub fn new(id: u32) -> Self {
-        let (user, nice, system, idel) = Self::read_status(id).unwrap();
+        Self::try_new(id).unwrap()
+    }
+
+    /// Create a new CPU usage monitor widget for specified core, or fail if the core can not be
+    /// read
+    ///
+    /// **id** The core id
+    pub fn try_new(id: u32) -> crate::Result<Self> {
+        let (user, nice, system, idel) = Self::read_status(id)
+            .map_err(|e| Error::Other(format!("Cannot read CPU core {}: {}", id, e)))?;
         let ret = Self {
             id,
             user,
@@ -59,16 +72,16 @@ impl CpuWidget {
             system_color: "#ff0000".to_string(),
         };
 
-        return ret;
+        Ok(ret)
     }
 
-    fn draw_bar(&mut self) -> Option<String> {
+    fn draw_bar(&mut self) -> Result<String> {
         let mut ret = Vec::new();
         for _ in 0..self.width {
             ret.push("<span foreground=\"grey\">|</span>".to_string());
         }
 
-        let (user, nice, system, idel) = Self::read_status(self.id).ok()?;
+        let (user, nice, system, idel) = Self::read_status(self.id)?;
 
         let total_diff =
             (user + nice + system + idel) - (self.user + self.nice + self.system + self.idel);
@@ -96,24 +109,22 @@ impl CpuWidget {
         self.idel = idel;
         self.system = system;
 
-        return Some(result);
+        return Ok(result);
     }
 }
 
 impl Widget for CpuWidget {
-    fn update(&mut self) -> Option<WidgetUpdate> {
-        if let Some(bar) = self.draw_bar() {
-            let mut data = Block::new();
-
-            data.use_pango();
-            data.append_full_text(&format!("{}[{}]", self.id + 1, bar));
-
-            return Some(WidgetUpdate {
-                refresh_interval: std::time::Duration::new(1, 0),
-                data: Some(data),
-            });
-        }
-
-        None
+    fn try_update(&mut self) -> crate::Result<Option<WidgetUpdate>> {
+        let bar = self.draw_bar()?;
+        let mut data = Block::new();
+
+        data.use_pango();
+        data.append_full_text(&format!("{}[{}]", self.id + 1, bar));
+
+        Ok(Some(WidgetUpdate {
+            refresh_interval: std::time::Duration::new(1, 0),
+            aligned: false,
+            data: Some(data.into()),
+        }))
     }
 }
@@ -23,7 +23,8 @@ impl Widget for DateTimeWidget {
 
         Some(WidgetUpdate {
             refresh_interval: std::time::Duration::new(1, 0),
-            data: Some(Block::new().append_full_text(&time_string).clone()),
+            aligned: true,
+            data: Some(Block::new().append_full_text(&time_string).clone().into()),
         })
     }
 }
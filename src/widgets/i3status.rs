@@ -0,0 +1,148 @@
+use crate::error::{Error, Result};
+use crate::protocol::{parse_blocks, Block, ClickEvent, Header};
+use crate::widget::{Notifier, Widget, WidgetData, WidgetUpdate};
+use crate::widgets::process::{Output, Supervisor};
+
+use std::time::Duration;
+
+/// What the status program declared in its protocol header
+enum Mode {
+    /// The header hasn't been read yet
+    Starting,
+    /// The program speaks the i3bar protocol
+    Blocks {
+        /// If the program wants the click events
+        click_events: bool,
+        /// If the click event array has been opened
+        clicking: bool,
+    },
+    /// The program writes plain text lines, which i3bar accepts as well
+    Text,
+}
+
+/// The widget that runs i3status, or any other program writing the i3bar protocol, and shows its
+/// blocks in place on the bar
+///
+/// This makes it possible to keep using a few i3status modules while moving to i3monkit one module
+/// at a time. The program is started with `sh -c`, its protocol header and infinite block array are
+/// parsed, and the blocks of the latest array replace the blocks of the widget as soon as they are
+/// written. A program that doesn't write the protocol header is read as plain text, one line at a
+/// time.
+///
+/// The blocks keep their names and instances, so the clicks on them are written to the standard
+/// input of the program if it enabled the click events in its header. The program is restarted if
+/// it exits.
+///
+/// ```no_run
+/// use i3monkit::widgets::{CpuWidget, I3StatusWidget};
+/// use i3monkit::{Header, I3Protocol, WidgetCollection};
+///
+/// let mut bar = WidgetCollection::new();
+/// bar.push(I3StatusWidget::new("i3status -c ~/.config/i3status/ipv6.conf"));
+/// bar.push(CpuWidget::new(0));
+/// bar.update_loop(I3Protocol::new(Header::new(1), std::io::stdout()));
+/// ```
+pub struct I3StatusWidget {
+    command: String,
+    process: Option<Supervisor>,
+    mode: Mode,
+}
+
+impl Default for I3StatusWidget {
+    fn default() -> Self {
+        Self::new("i3status")
+    }
+}
+
+impl I3StatusWidget {
+    /// Create the widget, the program is started once the widget is pushed to the bar
+    ///
+    /// **command** The shell command running the status program, for example `i3status`
+    pub fn new(command: &str) -> Self {
+        Self {
+            command: command.to_string(),
+            process: None,
+            mode: Mode::Starting,
+        }
+    }
+
+    /// Read a line written by the program, returns the new blocks if there's any
+    fn parse(&mut self, line: &str) -> Option<WidgetData> {
+        if let Mode::Starting = self.mode {
+            match serde_json::from_str::<Header>(line) {
+                Ok(header) => {
+                    self.mode = Mode::Blocks {
+                        click_events: header.click_events_enabled(),
+                        clicking: false,
+                    };
+                    return None;
+                }
+                Err(_) => self.mode = Mode::Text,
+            }
+        }
+
+        match self.mode {
+            Mode::Text if line.is_empty() => Some(WidgetData::Hidden),
+            Mode::Text => Some(Block::new().full_text(line).clone().into()),
+            _ => parse_blocks(line).map(|blocks| {
+                if blocks.is_empty() {
+                    WidgetData::Hidden
+                } else {
+                    blocks.into()
+                }
+            }),
+        }
+    }
+}
+
+impl Widget for I3StatusWidget {
+    fn try_update(&mut self) -> Result<Option<WidgetUpdate>> {
+        let command = &self.command;
+        let output: Vec<_> = self
+            .process
+            .get_or_insert_with(|| Supervisor::spawn(command, None))
+            .output()
+            .collect();
+
+        let mut data = None;
+        for output in output {
+            match output {
+                Output::Started => self.mode = Mode::Starting,
+                Output::Line(line) => data = self.parse(&line).or(data),
+                Output::Failed(what) => return Err(Error::Other(what)),
+            }
+        }
+
+        // The widget is notified for every line, this is just a fallback
+        Ok(Some(WidgetUpdate {
+            refresh_interval: Duration::from_secs(60),
+            aligned: false,
+            data,
+        }))
+    }
+
+    fn on_click(&mut self, event: &ClickEvent) {
+        let (process, json) = match (&self.process, serde_json::to_string(event)) {
+            (Some(process), Ok(json)) => (process, json),
+            _ => return,
+        };
+
+        if let Mode::Blocks {
+            click_events: true,
+            ref mut clicking,
+        } = self.mode
+        {
+            if *clicking {
+                process.write_line(&format!(",{}", json));
+            } else {
+                *clicking = process.write_line("[") && process.write_line(&json);
+            }
+        }
+    }
+
+    fn set_notifier(&mut self, notifier: Notifier) {
+        if self.process.is_none() {
+            self.process = Some(Supervisor::spawn(&self.command, Some(notifier)));
+        }
+    }
+}
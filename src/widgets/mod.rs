@@ -1,13 +1,21 @@
 mod battery;
+mod command;
 mod cpu;
 mod datetime;
+mod i3status;
 mod network;
+mod process;
 mod stock;
+mod stream;
 mod volume;
 
 pub use self::battery::BatteryWidget;
+pub use self::command::CommandWidget;
 pub use self::cpu::CpuWidget;
 pub use self::datetime::DateTimeWidget;
+pub use self::i3status::I3StatusWidget;
 pub use self::network::NetworkSpeedWidget;
+pub(crate) use self::stock::StockTicker;
 pub use self::stock::{StockClient, StockWidget};
+pub use self::stream::StreamWidget;
 pub use self::volume::VolumeWidget;
@@ -1,11 +1,12 @@
-use crate::protocol::Block;
-use crate::widget::{Widget, WidgetUpdate};
+use crate::error::Error as WidgetError;
+use crate::protocol::{Block, ColorRGB};
+use crate::widget::{Widget, WidgetData, WidgetUpdate};
 
 use std::path::PathBuf;
 
 use std::fs::File;
 use std::io::{BufRead, BufReader, Error, ErrorKind, Result};
-use std::time::SystemTime;
+use std::time::Instant;
 
 const NETWORK_PATH_PREFIX: &'static str = "/sys/class/net";
 const NETWORK_STAT_SUFFIX: &'static str = "statistics/dummy";
@@ -13,7 +14,7 @@ const NETWORK_STAT_SUFFIX: &'static str = "statistics/dummy";
 struct TransferStat {
     rx: u64,
     tx: u64,
-    ts: SystemTime,
+    ts: Instant,
 }
 
 impl TransferStat {
@@ -36,15 +37,22 @@ impl TransferStat {
             return Err(Error::new(ErrorKind::Other, "Empty file"));
         };
 
-        let rx = u64::from_str_radix(&(read_stat_file("rx_bytes")?), 10).unwrap();
-        let tx = u64::from_str_radix(&(read_stat_file("tx_bytes")?), 10).unwrap();
-        let ts = SystemTime::now();
+        let parse = |value: String| {
+            value
+                .trim()
+                .parse::<u64>()
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e))
+        };
+
+        let rx = parse(read_stat_file("rx_bytes")?)?;
+        let tx = parse(read_stat_file("tx_bytes")?)?;
+        let ts = Instant::now();
 
         return Ok(Self { rx, tx, ts });
     }
 
     fn duration(&self, earlier: &Self) -> f64 {
-        let duration = self.ts.duration_since(earlier.ts).unwrap();
+        let duration = self.ts.duration_since(earlier.ts);
         let secs = duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1_000_000_000.0;
         return secs;
     }
@@ -72,18 +80,50 @@ impl TransferStat {
 pub struct NetworkSpeedWidget {
     interface: String,
     last_stat: TransferStat,
+    hide_when_down: bool,
+    hidden: bool,
 }
 
 impl NetworkSpeedWidget {
     /// Create the widget, for given interface.
     ///
     /// **interface** The interface to monitor
+    ///
+    /// Panics if the interface doesn't exist, see `try_new`.
     pub fn new(interface: &str) -> Self {
-        let last_stat = TransferStat::read_stat(interface).unwrap();
+        Self::try_new(interface).unwrap()
+    }
+
+    /// Create the widget for given interface, or fail if the interface can not be read
+    ///
+    /// **interface** The interface to monitor
+    pub fn try_new(interface: &str) -> crate::Result<Self> {
+        let last_stat = TransferStat::read_stat(interface).map_err(|e| {
+            WidgetError::Other(format!(
+                "Cannot read network interface {}: {}",
+                interface, e
+            ))
+        })?;
         let interface = interface.to_string();
-        Self {
+        Ok(Self {
             last_stat,
             interface,
+            hide_when_down: false,
+            hidden: false,
+        })
+    }
+
+    /// Hide the widget while the interface is down, it shows up again once the interface is up
+    pub fn hide_when_down(mut self, hide: bool) -> Self {
+        self.hide_when_down = hide;
+        self
+    }
+
+    fn is_up(&self) -> bool {
+        let path = format!("{}/{}/operstate", NETWORK_PATH_PREFIX, self.interface);
+        match std::fs::read_to_string(path) {
+            Ok(state) => state.trim() != "down",
+            Err(_) => false,
         }
     }
 
@@ -123,16 +163,43 @@ impl NetworkSpeedWidget {
 }
 
 impl Widget for NetworkSpeedWidget {
-    fn update(&mut self) -> Option<WidgetUpdate> {
-        if let Ok((rx, tx)) = self.get_human_readable_stat() {
-            let mut data = Block::new();
-            data.use_pango();
-            data.append_full_text(&format!("Rx:<tt>{}</tt> Tx:<tt>{}</tt>", rx, tx));
-            return Some(WidgetUpdate {
-                refresh_interval: std::time::Duration::new(1, 0),
-                data: Some(data),
-            });
+    fn try_update(&mut self) -> crate::Result<Option<WidgetUpdate>> {
+        if self.hide_when_down {
+            if !self.is_up() {
+                self.hidden = true;
+                return Ok(Some(WidgetUpdate {
+                    refresh_interval: std::time::Duration::new(1, 0),
+                    aligned: false,
+                    data: Some(WidgetData::Hidden),
+                }));
+            }
+
+            // The counters may have been reset while the interface was down
+            if self.hidden {
+                if let Ok(stat) = TransferStat::read_stat(&self.interface) {
+                    self.last_stat = stat;
+                }
+                self.hidden = false;
+            }
         }
-        None
+
+        let (rx, tx) = self.get_human_readable_stat()?;
+
+        let mut data = Block::new();
+        data.use_pango();
+        data.append_full_text(&format!("Rx:<tt>{}</tt> Tx:<tt>{}</tt>", rx, tx));
+
+        Ok(Some(WidgetUpdate {
+            refresh_interval: std::time::Duration::new(1, 0),
+            aligned: false,
+            data: Some(data.into()),
+        }))
+    }
+
+    fn error_block(&self, _error: &WidgetError) -> Option<Block> {
+        let mut block = Block::new();
+        block.append_full_text(&format!("{}: ERR", self.interface));
+        block.color(ColorRGB::red());
+        Some(block)
     }
 }
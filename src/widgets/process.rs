@@ -0,0 +1,197 @@
+use crate::widget::Notifier;
+
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender, TryIter};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How long to wait before the first restart of the command
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+/// The longest wait before restarting the command
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// What happened to the child process
+pub(crate) enum Output {
+    /// The command has been started again, the following lines come from the new process
+    Started,
+    /// A line printed by the command
+    Line(String),
+    /// The command couldn't be started
+    Failed(String),
+}
+
+/// The process shared between the widget and the thread reading its output
+#[derive(Default)]
+struct Shared {
+    child: Mutex<Option<Child>>,
+    stdin: Mutex<Option<ChildStdin>>,
+    stopped: AtomicBool,
+}
+
+/// Runs a shell command on its own thread and restarts it when it exits
+///
+/// If the command exits, it's started again after 1 second, doubling the wait up to 5 minutes for
+/// as long as the command exits without printing anything. The command, along with the processes
+/// it started, is killed when the supervisor is dropped.
+pub(crate) struct Supervisor {
+    shared: Arc<Shared>,
+    output: Receiver<Output>,
+}
+
+impl Supervisor {
+    /// Start the command with `sh -c`
+    ///
+    /// **command** The shell command to run
+    /// **notifier** The notifier called for every output of the command
+    pub(crate) fn spawn(command: &str, notifier: Option<Notifier>) -> Self {
+        let (output_tx, output) = channel();
+        let shared = Arc::new(Shared::default());
+
+        let command = command.to_string();
+        let thread_shared = shared.clone();
+        std::thread::spawn(move || run(&command, &thread_shared, &output_tx, notifier.as_ref()));
+
+        Supervisor { shared, output }
+    }
+
+    /// The output of the command received since the last call
+    pub(crate) fn output(&self) -> TryIter<'_, Output> {
+        self.output.try_iter()
+    }
+
+    /// Write a line to the standard input of the command, returns false if the command isn't
+    /// running
+    pub(crate) fn write_line(&self, line: &str) -> bool {
+        match *self.shared.stdin.lock().unwrap() {
+            Some(ref mut stdin) => writeln!(stdin, "{}", line)
+                .and_then(|_| stdin.flush())
+                .is_ok(),
+            None => false,
+        }
+    }
+}
+
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::SeqCst);
+        if let Some(ref mut child) = *self.shared.child.lock().unwrap() {
+            kill(child);
+        }
+    }
+}
+
+/// Kill the command along with every process it started, such as the commands of a pipeline
+///
+/// The command is the leader of its own process group, which the processes it starts join.
+fn kill(child: &mut Child) {
+    unsafe {
+        libc::killpg(child.id() as libc::pid_t, libc::SIGKILL);
+    }
+}
+
+/// Keep the command running and send every line it prints, until the supervisor is dropped
+fn run(command: &str, shared: &Shared, output: &Sender<Output>, notifier: Option<&Notifier>) {
+    let send = |event| {
+        let sent = output.send(event).is_ok();
+        if let Some(notifier) = notifier {
+            notifier.notify();
+        }
+        sent
+    };
+
+    let mut sh = Command::new("sh");
+    sh.arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .process_group(0);
+
+    // In its own process group, the command misses the signal i3bar sends to the group of the bar
+    // when the bar goes away, so the kernel terminates the command once the bar is gone instead
+    let parent = unsafe { libc::getpid() };
+    unsafe {
+        sh.pre_exec(move || {
+            if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM as libc::c_ulong) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            // The bar may have died before the signal is set up
+            if libc::getppid() != parent {
+                libc::_exit(0);
+            }
+            Ok(())
+        });
+    }
+
+    let mut backoff = MIN_BACKOFF;
+
+    while !shared.stopped.load(Ordering::SeqCst) {
+        let mut child = match sh.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                if !send(Output::Failed(e.to_string())) {
+                    return;
+                }
+                std::thread::sleep(backoff);
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                continue;
+            }
+        };
+
+        let stdout = child.stdout.take();
+        *shared.stdin.lock().unwrap() = child.stdin.take();
+        *shared.child.lock().unwrap() = Some(child);
+
+        // The supervisor may have been dropped before the child is stored
+        if shared.stopped.load(Ordering::SeqCst) || output.send(Output::Started).is_err() {
+            break;
+        }
+
+        let mut printed = false;
+        let mut reader = BufReader::new(stdout.unwrap());
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            match reader.read_until(b'\n', &mut buf) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            }
+
+            // A line that isn't valid UTF-8 is shown as is, instead of restarting the command
+            let line = String::from_utf8_lossy(&buf);
+            let line = line.strip_suffix('\n').unwrap_or(&line);
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            printed = true;
+            if !send(Output::Line(line.to_string())) {
+                break;
+            }
+        }
+
+        // Nothing is left to read, the processes that are still running are of no use
+        *shared.stdin.lock().unwrap() = None;
+        if let Some(mut child) = shared.child.lock().unwrap().take() {
+            kill(&mut child);
+            child.wait().ok();
+        }
+
+        if shared.stopped.load(Ordering::SeqCst) {
+            break;
+        }
+
+        if printed {
+            backoff = MIN_BACKOFF;
+        }
+        std::thread::sleep(backoff);
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+
+    // The supervisor is gone, make sure the child doesn't outlive it
+    if let Some(mut child) = shared.child.lock().unwrap().take() {
+        kill(&mut child);
+        child.wait().ok();
+    }
+}
@@ -1,5 +1,5 @@
-use crate::protocol::Block;
-use crate::widget::{Widget, WidgetUpdate};
+use crate::protocol::{Block, ClickEvent};
+use crate::widget::{Notifier, Widget, WidgetUpdate};
 
 use curl::easy::Easy;
 use serde::Deserialize;
@@ -32,11 +32,19 @@ use std::time::{Duration, SystemTime};
 ///
 pub struct StockClient<'a> {
     symbols: Vec<&'a str>,
-    api_key: &'a str,
+    feed: PriceFeed,
+}
+
+/// The prices of the symbols, which are queried on a background thread
+struct PriceFeed {
+    symbols: Vec<String>,
+    api_key: String,
     cache: HashMap<String, StockPrice>,
     refresh_thread: Option<JoinHandle<()>>,
     refresh_channel: Option<Receiver<HashMap<String, StockPrice>>>,
+    notifiers: Vec<Notifier>,
 }
+
 #[derive(Deserialize, Debug)]
 struct RawStockPrice {
     #[serde(rename = "1. open")]
@@ -68,45 +76,79 @@ struct StockPrice {
 }
 
 /// The widget for realtime stock price
+///
+/// Clicking the widget cycles through all the symbols of the client.
 pub struct StockWidget<'a> {
     symbol: &'a str,
     client: Rc<RefCell<StockClient<'a>>>,
 }
 
+/// The widget for the realtime stock prices of several symbols, with one block per symbol
+pub(crate) struct StockTicker {
+    feed: PriceFeed,
+}
+
 impl<'a> Widget for StockWidget<'a> {
     fn update(&mut self) -> Option<WidgetUpdate> {
-        self.client.borrow_mut().refresh();
-        let mut block = Block::new();
-        block.use_pango();
-        block.append_full_text(&format!(
-            "<span foreground=\"#eaeaea\">{} </span>",
-            self.symbol
-        ));
-        if let Some(latest) = self.client.borrow().cache.get(&self.symbol.to_string()) {
-            let color = if latest.previous_close > latest.close {
-                "#ff0000"
-            } else if latest.previous_close < latest.close {
-                "#00ff00"
-            } else {
-                "#ffffff"
-            };
-
-            block.append_full_text(&format!(
-                "<span foreground=\"{color}\">{value:.2}({percent:.1}%)</span>",
-                color = color,
-                value = latest.close,
-                percent =
-                    100.0 * (latest.close - latest.previous_close).abs() / latest.previous_close
-            ));
-        } else {
-            block.append_full_text("<span foreground=\"#777777\">waiting</span>");
-        }
+        self.client.borrow_mut().feed.refresh();
+        let block = self.client.borrow().feed.block(self.symbol);
 
+        // The widget is notified when new prices arrive, this is just a fallback
         return Some(WidgetUpdate {
-            refresh_interval: std::time::Duration::new(1, 0),
-            data: Some(block),
+            refresh_interval: std::time::Duration::new(60, 0),
+            aligned: false,
+            data: Some(block.into()),
         });
     }
+
+    fn set_notifier(&mut self, notifier: Notifier) {
+        self.client.borrow_mut().feed.notifiers.push(notifier);
+    }
+
+    fn on_click(&mut self, event: &ClickEvent) {
+        if event.button != 1 {
+            return;
+        }
+
+        let client = self.client.borrow();
+        if let Some(pos) = client.symbols.iter().position(|s| *s == self.symbol) {
+            self.symbol = client.symbols[(pos + 1) % client.symbols.len()];
+        }
+    }
+}
+
+impl StockTicker {
+    /// Creates the widget for the symbols, querying the prices with the API key
+    pub(crate) fn new(api_key: &str, symbols: &[String]) -> Self {
+        let mut feed = PriceFeed::new(api_key);
+        feed.symbols.extend_from_slice(symbols);
+        StockTicker { feed }
+    }
+
+    fn blocks(&self) -> Vec<Block> {
+        self.feed
+            .symbols
+            .iter()
+            .map(|symbol| self.feed.block(symbol))
+            .collect()
+    }
+}
+
+impl Widget for StockTicker {
+    fn update(&mut self) -> Option<WidgetUpdate> {
+        self.feed.refresh();
+
+        // The widget is notified when new prices arrive, this is just a fallback
+        Some(WidgetUpdate {
+            refresh_interval: Duration::new(60, 0),
+            aligned: false,
+            data: Some(self.blocks().into()),
+        })
+    }
+
+    fn set_notifier(&mut self, notifier: Notifier) {
+        self.feed.notifiers.push(notifier);
+    }
 }
 
 impl<'a> StockClient<'a> {
@@ -114,10 +156,7 @@ impl<'a> StockClient<'a> {
     pub fn new(api_key: &'a str) -> Rc<RefCell<Self>> {
         let client = Self {
             symbols: Vec::new(),
-            api_key,
-            cache: HashMap::new(),
-            refresh_thread: None,
-            refresh_channel: None,
+            feed: PriceFeed::new(api_key),
         };
         return Rc::new(RefCell::new(client));
     }
@@ -135,6 +174,51 @@ impl<'a> StockClient<'a> {
         };
     }
 
+    fn push(&mut self, symbol: &'a str) {
+        self.symbols.push(symbol);
+        self.feed.symbols.push(symbol.to_string());
+    }
+}
+
+impl PriceFeed {
+    fn new(api_key: &str) -> Self {
+        PriceFeed {
+            symbols: Vec::new(),
+            api_key: api_key.to_string(),
+            cache: HashMap::new(),
+            refresh_thread: None,
+            refresh_channel: None,
+            notifiers: Vec::new(),
+        }
+    }
+
+    /// The block showing the latest price of the symbol
+    fn block(&self, symbol: &str) -> Block {
+        let mut block = Block::new();
+        block.use_pango().instance(symbol);
+        block.append_full_text(&format!("<span foreground=\"#eaeaea\">{} </span>", symbol));
+        if let Some(latest) = self.cache.get(symbol) {
+            let color = if latest.previous_close > latest.close {
+                "#ff0000"
+            } else if latest.previous_close < latest.close {
+                "#00ff00"
+            } else {
+                "#ffffff"
+            };
+
+            block.append_full_text(&format!(
+                "<span foreground=\"{color}\">{value:.2}({percent:.1}%)</span>",
+                color = color,
+                value = latest.close,
+                percent =
+                    100.0 * (latest.close - latest.previous_close).abs() / latest.previous_close
+            ));
+        } else {
+            block.append_full_text("<span foreground=\"#777777\">waiting</span>");
+        }
+        block
+    }
+
     fn ensure_refresh_started(&mut self) {
         if self.refresh_thread.is_none() {
             let mut symbols: Vec<_> = self
@@ -143,6 +227,7 @@ impl<'a> StockClient<'a> {
                 .map(|x| (x.to_string(), SystemTime::now()))
                 .collect();
             let api_key = self.api_key.to_string();
+            let notifiers = self.notifiers.clone();
 
             let (sx, rx) = std::sync::mpsc::channel();
 
@@ -164,7 +249,15 @@ impl<'a> StockClient<'a> {
 
                 symbols.sort_by_key(|(_, ts)| *ts);
 
-                sx.send(data).ok();
+                // Only the new prices are sent, the widget keeps the old ones in the meantime. Once
+                // the widgets are gone, for example the configuration is reloaded, the thread ends.
+                let has_news = !data.is_empty();
+                if sx.send(data).is_err() {
+                    return;
+                }
+                if has_news {
+                    notifiers.iter().for_each(Notifier::notify);
+                }
 
                 let next_wakeup = symbols.iter().min_by_key(|(_, ts)| ts).unwrap();
 
@@ -186,7 +279,7 @@ impl<'a> StockClient<'a> {
     fn refresh(&mut self) {
         self.ensure_refresh_started();
         if let Some(ref mut rx) = self.refresh_channel {
-            if let Ok(new_data) = rx.try_recv() {
+            for new_data in rx.try_iter() {
                 for (k, v) in new_data {
                     self.cache.insert(k, v);
                 }
@@ -194,10 +287,6 @@ impl<'a> StockClient<'a> {
         }
     }
 
-    fn push(&mut self, symbol: &'a str) {
-        self.symbols.push(symbol);
-    }
-
     fn query_latest(symbol: &str, key: &str) -> Option<StockPrice> {
         let url = format!("https://{server}/query?function=TIME_SERIES_DAILY_ADJUSTED&symbol={symbol}&interval=5min&outputsize=compact&apikey={key}",
                           server = "www.alphavantage.co", symbol = symbol, key = key);
@@ -229,7 +318,7 @@ impl<'a> StockClient<'a> {
                         .filter(|d| d != &latest_date)
                         .max();
 
-                    let open = f32::from_str(&latest.open).unwrap();
+                    let open = f32::from_str(&latest.open).ok()?;
 
                     let pc = if let Some(yesterday) = yesterday {
                         let yesterday = &response.time_series[yesterday];
@@ -253,3 +342,27 @@ impl<'a> StockClient<'a> {
         return None;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_symbol_is_queried() {
+        let symbols = vec!["MSFT".to_string(), "AMD".to_string(), "FB".to_string()];
+        let ticker = StockTicker::new("key", &symbols);
+        assert_eq!(ticker.feed.symbols, symbols);
+
+        let instances: Vec<_> = ticker
+            .blocks()
+            .iter()
+            .map(|block| block.get_instance().to_string())
+            .collect();
+        assert_eq!(instances, symbols);
+
+        let client = StockClient::new("key");
+        StockClient::create_widget(&client, "MSFT");
+        StockClient::create_widget(&client, "AMD");
+        assert_eq!(client.borrow().feed.symbols, ["MSFT", "AMD"]);
+    }
+}
@@ -0,0 +1,111 @@
+use crate::error::{Error, Result};
+use crate::protocol::{Block, ClickEvent};
+use crate::widget::{Notifier, Widget, WidgetData, WidgetUpdate};
+use crate::widgets::process::{Output, Supervisor};
+
+use std::time::Duration;
+
+/// The widget that shows the output of a command that keeps running, one line at a time
+///
+/// The command is run with `sh -c` and every line it prints replaces the block right away, so a
+/// script waiting for events, such as `xtitle -s` or `pactl subscribe` piped through a formatter,
+/// doesn't need to be polled. A line starting with `{` is parsed as a block in the JSON format of
+/// the i3bar protocol, anything else is the full text of the block. An empty line hides the block.
+///
+/// If the command exits, it's started again after 1 second, doubling the wait up to 5 minutes for
+/// as long as the command exits without printing anything. The last line stays on the bar in the
+/// meantime.
+///
+/// Clicks are written to the standard input of the command as JSON objects, one per line, like
+/// the persistent blocklets of i3blocks.
+///
+/// ```no_run
+/// use i3monkit::widgets::StreamWidget;
+/// use i3monkit::{Header, I3Protocol, WidgetCollection};
+///
+/// let mut bar = WidgetCollection::new();
+/// bar.push(StreamWidget::new("xtitle -s"));
+/// bar.update_loop(I3Protocol::new(Header::new(1), std::io::stdout()));
+/// ```
+pub struct StreamWidget {
+    command: String,
+    pango: bool,
+    process: Option<Supervisor>,
+}
+
+impl StreamWidget {
+    /// Create the widget, the command is started once the widget is pushed to the bar
+    ///
+    /// **command** The shell command to run
+    pub fn new(command: &str) -> Self {
+        Self {
+            command: command.to_string(),
+            pango: false,
+            process: None,
+        }
+    }
+
+    /// Render the plain text lines as Pango markup
+    pub fn use_pango(mut self) -> Self {
+        self.pango = true;
+        self
+    }
+
+    fn parse(&self, line: String) -> WidgetData {
+        if line.is_empty() {
+            return WidgetData::Hidden;
+        }
+
+        if line.starts_with('{') {
+            if let Ok(block) = serde_json::from_str::<Block>(&line) {
+                return block.into();
+            }
+        }
+
+        let mut block = Block::new();
+        block.full_text(&line);
+        if self.pango {
+            block.use_pango();
+        }
+        block.into()
+    }
+}
+
+impl Widget for StreamWidget {
+    fn try_update(&mut self) -> Result<Option<WidgetUpdate>> {
+        let command = &self.command;
+        let process = self
+            .process
+            .get_or_insert_with(|| Supervisor::spawn(command, None));
+
+        let latest = process
+            .output()
+            .filter(|output| !matches!(output, Output::Started))
+            .last();
+
+        let data = match latest {
+            Some(Output::Line(line)) => Some(self.parse(line)),
+            Some(Output::Failed(what)) => return Err(Error::Other(what)),
+            _ => None,
+        };
+
+        // The widget is notified for every line, this is just a fallback
+        Ok(Some(WidgetUpdate {
+            refresh_interval: Duration::from_secs(60),
+            aligned: false,
+            data,
+        }))
+    }
+
+    fn on_click(&mut self, event: &ClickEvent) {
+        if let (Some(process), Ok(json)) = (&self.process, serde_json::to_string(event)) {
+            process.write_line(&json);
+        }
+    }
+
+    fn set_notifier(&mut self, notifier: Notifier) {
+        if self.process.is_none() {
+            self.process = Some(Supervisor::spawn(&self.command, Some(notifier)));
+        }
+    }
+}
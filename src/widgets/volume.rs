@@ -1,4 +1,4 @@
-use crate::protocol::{Block, ColorRGB};
+use crate::protocol::{Block, ClickEvent, ColorRGB};
 use crate::widget::{Widget, WidgetUpdate};
 
 use alsa::mixer::{Mixer, Selem, SelemChannelId, SelemId};
@@ -7,6 +7,8 @@ use alsa::Result;
 use std::ffi::CString;
 
 /// The system volume widget
+///
+/// Clicking the widget toggles the mute switch of the mixer.
 pub struct VolumeWidget {
     device: CString,
     #[allow(dead_code)]
@@ -15,11 +17,16 @@ pub struct VolumeWidget {
 }
 
 impl VolumeWidget {
-    fn get_volume(&self) -> Result<Option<(bool, u32)>> {
+    fn open_mixer(&self) -> Result<Mixer> {
         let mut handle = Mixer::open(false)?;
         handle.attach(self.device.as_c_str())?;
         Selem::register(&mut handle)?;
         handle.load()?;
+        Ok(handle)
+    }
+
+    fn get_volume(&self) -> Result<Option<(bool, u32)>> {
+        let handle = self.open_mixer()?;
 
         if let Some(selem) = handle.find_selem(&self.selem_id) {
             let (min, max) = selem.get_playback_volume_range();
@@ -32,6 +39,17 @@ impl VolumeWidget {
         return Ok(None);
     }
 
+    fn toggle_mute(&self) -> Result<()> {
+        let handle = self.open_mixer()?;
+
+        if let Some(selem) = handle.find_selem(&self.selem_id) {
+            let switch = selem.get_playback_switch(SelemChannelId::FrontLeft)?;
+            selem.set_playback_switch_all(if switch == 0 { 1 } else { 0 })?;
+        }
+
+        Ok(())
+    }
+
     /// Creates new widget for the given mixer and channel id
     pub fn new(device: &str, mixer: &str, idx: u32) -> Self {
         let device = CString::new(device).unwrap();
@@ -50,8 +68,12 @@ impl VolumeWidget {
 }
 
 impl Widget for VolumeWidget {
-    fn update(&mut self) -> Option<WidgetUpdate> {
-        if let Ok(Some((mute, vol))) = self.get_volume() {
+    fn try_update(&mut self) -> crate::Result<Option<WidgetUpdate>> {
+        let volume = self
+            .get_volume()
+            .map_err(|e| crate::Error::Other(e.to_string()))?;
+
+        if let Some((mute, vol)) = volume {
             let icon = if !mute { "🔊" } else { "🔇" };
             let status = format!("{}%{}", vol, icon);
             let mut data = Block::new().append_full_text(&status).clone();
@@ -59,12 +81,19 @@ impl Widget for VolumeWidget {
                 data.color(ColorRGB::yellow());
             }
 
-            return Some(WidgetUpdate {
+            return Ok(Some(WidgetUpdate {
                 refresh_interval: std::time::Duration::new(1, 0),
-                data: Some(data),
-            });
+                aligned: false,
+                data: Some(data.into()),
+            }));
         }
 
-        None
+        Ok(None)
+    }
+
+    fn on_click(&mut self, event: &ClickEvent) {
+        if event.button == 1 {
+            self.toggle_mute().ok();
+        }
     }
 }
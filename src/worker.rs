@@ -0,0 +1,71 @@
+//! The background thread that updates a widget without blocking the bar
+
+use crate::protocol::ClickEvent;
+use crate::widget::{click_widget, Message, UpdateResult, Widget};
+use std::sync::mpsc::{channel, Sender};
+use std::time::Duration;
+
+enum Request {
+    Update,
+    Click(ClickEvent),
+}
+
+/// A widget that is updated on its own thread
+pub(crate) struct Worker {
+    requests: Sender<Request>,
+    /// If there's an update requested but not delivered yet
+    pub(crate) busy: bool,
+    /// How long the framework waits for an update before the block is marked stale
+    pub(crate) timeout: Duration,
+}
+
+impl Worker {
+    /// Move the widget to a new thread
+    ///
+    /// **id** The id of the widget in the collection
+    /// **timeout** The timeout for each update
+    /// **result_tx** Where the update results should be sent
+    pub(crate) fn spawn<W: Widget + Send + 'static>(
+        mut widget: W,
+        id: usize,
+        timeout: Duration,
+        result_tx: Sender<Message>,
+    ) -> Self {
+        let (requests, rx) = channel();
+
+        std::thread::spawn(move || {
+            for request in rx {
+                if let Request::Click(event) = request {
+                    click_widget(&mut widget, &event);
+                }
+
+                let result = UpdateResult::of(&mut widget);
+                if result_tx.send(Message::Updated(id, result)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Worker {
+            requests,
+            busy: false,
+            timeout,
+        }
+    }
+
+    fn send(&mut self, request: Request) {
+        if self.requests.send(request).is_ok() {
+            self.busy = true;
+        }
+    }
+
+    /// Ask the widget for an update
+    pub(crate) fn request_update(&mut self) {
+        self.send(Request::Update);
+    }
+
+    /// Pass the click event to the widget, which will be updated afterwards
+    pub(crate) fn click(&mut self, event: &ClickEvent) {
+        self.send(Request::Click(event.clone()));
+    }
+}